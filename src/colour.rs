@@ -0,0 +1,154 @@
+use super::deser::Colour;
+
+// Colours get stored in the database as a bit for each colour, so that "has at least these
+//  colours" and "fits in this identity" are quick bitwise checks in SQL.
+const WHITE: u8 = 1;
+const BLUE: u8 = 2;
+const BLACK: u8 = 4;
+const RED: u8 = 8;
+const GREEN: u8 = 16;
+// Only really means something for produced mana
+const COLOURLESS: u8 = 32;
+
+// https://mtg.fandom.com/wiki/Color#Color_combinations
+const COLOUR_COMBINATION_NAMES: [(&str, &str); 25] = [
+    ("white", "w"),
+    ("blue", "u"),
+    ("black", "b"),
+    ("red", "r"),
+    ("green", "g"),
+    ("azorius", "wu"),
+    ("dimir", "ub"),
+    ("rakdos", "br"),
+    ("gruul", "rg"),
+    ("selesnya", "gw"),
+    ("orzhov", "wb"),
+    ("izzet", "ur"),
+    ("golgari", "bg"),
+    ("boros", "rw"),
+    ("simic", "gu"),
+    ("bant", "gwu"),
+    ("esper", "wub"),
+    ("grixis", "ubr"),
+    ("jund", "brg"),
+    ("naya", "rgw"),
+    ("abzan", "wbg"),
+    ("jeskai", "urw"),
+    ("sultai", "bgu"),
+    ("mardu", "rwb"),
+    ("temur", "gur"),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Colours(u8);
+
+impl Colours {
+    pub fn bits(&self) -> u8 {
+        self.0
+    }
+
+    pub fn from_scryfall(colours: &[Colour]) -> Colours {
+        let mut bits = 0;
+        for colour in colours {
+            bits |= match colour {
+                Colour::White => WHITE,
+                Colour::Blue => BLUE,
+                Colour::Black => BLACK,
+                Colour::Red => RED,
+                Colour::Green => GREEN,
+                Colour::Colourless => COLOURLESS,
+                // Sole Performer says it produces {T}... no idea why
+                Colour::Tap => 0,
+            };
+        }
+        Colours(bits)
+    }
+
+    pub fn colourless_mana() -> Colours {
+        Colours(COLOURLESS)
+    }
+
+    pub fn parse_letters(letters: &str) -> Option<Colours> {
+        let mut bits = 0;
+        for letter in letters.to_lowercase().chars() {
+            bits |= match letter {
+                'w' => WHITE,
+                'u' => BLUE,
+                'b' => BLACK,
+                'r' => RED,
+                'g' => GREEN,
+                'c' => COLOURLESS,
+                _ => return None,
+            };
+        }
+        Some(Colours(bits))
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum ColourSearch {
+    Colours(Colours),
+    Colourless,
+    Multicolour,
+    Count(u8),
+}
+
+// Understands letters (wu), names (blue), guilds, shards and wedges (esper), colourless (c),
+//  multicolour (m) and a number of colours (2)
+pub fn parse_colour_search(value: &str) -> Option<ColourSearch> {
+    let value = value.to_lowercase();
+    match value.as_str() {
+        "c" | "colorless" | "colourless" => return Some(ColourSearch::Colourless),
+        "m" | "multicolor" | "multicolour" => return Some(ColourSearch::Multicolour),
+        _ => (),
+    }
+    if let Ok(count) = value.parse::<u8>() {
+        return Some(ColourSearch::Count(count));
+    }
+    for (name, letters) in COLOUR_COMBINATION_NAMES {
+        if name == value {
+            return Colours::parse_letters(letters).map(ColourSearch::Colours);
+        }
+    }
+    Colours::parse_letters(&value).map(ColourSearch::Colours)
+}
+
+// Number of colours (not counting colourless) for a column of colour bits
+pub fn colour_count_sql(column: &str) -> String {
+    [WHITE, BLUE, BLACK, RED, GREEN]
+        .iter()
+        .map(|bit| format!("(({} & {}) > 0)", column, bit))
+        .collect::<Vec<String>>()
+        .join(" + ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_letters_and_names() {
+        assert_eq!(
+            parse_colour_search("wu"),
+            Some(ColourSearch::Colours(Colours(WHITE | BLUE)))
+        );
+        assert_eq!(
+            parse_colour_search("Esper"),
+            Some(ColourSearch::Colours(Colours(WHITE | BLUE | BLACK)))
+        );
+        assert_eq!(
+            parse_colour_search("green"),
+            Some(ColourSearch::Colours(Colours(GREEN)))
+        );
+        assert_eq!(parse_colour_search("c"), Some(ColourSearch::Colourless));
+        assert_eq!(parse_colour_search("m"), Some(ColourSearch::Multicolour));
+        assert_eq!(parse_colour_search("2"), Some(ColourSearch::Count(2)));
+        assert_eq!(parse_colour_search("purple"), None);
+    }
+
+    #[test]
+    fn from_scryfall_colours() {
+        let colours = Colours::from_scryfall(&[Colour::Green, Colour::Red, Colour::Colourless]);
+        assert_eq!(colours.bits(), GREEN | RED | COLOURLESS);
+    }
+}
@@ -1,11 +1,20 @@
-use deunicode::deunicode;
-use rusqlite::{Connection, Transaction, params, params_from_iter};
+use rusqlite::types::Value;
+use rusqlite::{Connection, Row, Transaction, params, params_from_iter};
 use std::cmp::Ordering;
+use std::collections::HashSet;
 use std::fmt;
 use std::fs;
 use std::path::PathBuf;
+use textdistance::str::damerau_levenshtein;
 
-use super::deser::{ScryfallCard, SetType};
+use super::colour::Colours;
+use super::deser::{Colour, Legality, ScryfallCard, ScryfallCardFaceObject, SetType};
+use super::mana::parse_mana_cost;
+use super::nickname::{BUNDLED_NICKNAMES, NicknameError, name_initialisms, parse_nickname_tsv};
+use super::normalise::normalise_search_text;
+use super::query::{Query, QueryError, compile_query, fts_expression, like_pattern};
+use super::stats::parse_stat;
+use super::type_line::{TypeLine, parse_type_line, split_faces};
 use super::utils::{create_local_data_folder, get_local_data_sqlite_file};
 
 pub fn get_all_card_names() -> Vec<String> {
@@ -32,15 +41,109 @@ pub fn get_all_mtg_words() -> Vec<String> {
     card_names
 }
 
-// unsure if this should be in this file...
-impl fmt::Display for DbCard {
+// Words from card names within max_distance edits (Damerau-Levenshtein) of the given word,
+//  closest first. An exact match comes back with a distance of 0.
+pub fn get_close_mtg_words(word: &str, max_distance: usize) -> Vec<(usize, String)> {
+    let conn = get_db_connection();
+    close_mtg_words(&conn, word, max_distance)
+}
+
+// Words are indexed for up to this many typos, so asking for more than this gets no more
+//  than this
+pub const MAX_SPELLING_DISTANCE: usize = 2;
+
+// Every way of taking up to max_deletions letters out of the word, including the word itself.
+//  Two words within that many typos of each other (a swap being one) always have one of these in
+//  common, so they can be looked up instead of checking every word, like SymSpell does.
+fn word_deletions(word: &str, max_deletions: usize) -> HashSet<String> {
+    let mut deletions = HashSet::from([word.to_string()]);
+    let mut last_round = vec![word.to_string()];
+    for _ in 0..max_deletions {
+        let mut this_round = Vec::new();
+        for word in last_round {
+            let chars: Vec<char> = word.chars().collect();
+            for i in 0..chars.len() {
+                let deletion: String = chars[..i].iter().chain(&chars[i + 1..]).collect();
+                if deletions.insert(deletion.clone()) {
+                    this_round.push(deletion);
+                }
+            }
+        }
+        last_round = this_round;
+    }
+    deletions
+}
+
+// The words that might be close enough, from the index. Still needs the real distance checking.
+fn spelling_candidates(conn: &Connection, word: &str, max_distance: usize) -> Vec<String> {
+    let deletions: Vec<String> = word_deletions(word, max_distance).into_iter().collect();
+    let placeholders: Vec<String> = (1..=deletions.len()).map(|i| format!("?{}", i)).collect();
+    let sql = format!(
+        "SELECT DISTINCT mtg_words.word FROM word_deletions JOIN mtg_words ON mtg_words.id = word_deletions.word_id
+         WHERE word_deletions.deletion IN ({});",
+        placeholders.join(", ")
+    );
+    let mut stmt = conn.prepare(&sql).unwrap();
+    stmt.query_map(params_from_iter(deletions), |row| row.get(0))
+        .unwrap()
+        .filter_map(|res| res.ok())
+        .collect()
+}
+
+fn close_mtg_words(conn: &Connection, word: &str, max_distance: usize) -> Vec<(usize, String)> {
+    let word = normalise_search_text(word);
+    let max_distance = max_distance.min(MAX_SPELLING_DISTANCE);
+    let mut close_words: Vec<(usize, String)> = spelling_candidates(conn, &word, max_distance)
+        .into_iter()
+        .map(|candidate| (damerau_levenshtein(&word, &candidate), candidate))
+        .filter(|(distance, _)| *distance <= max_distance)
+        .collect();
+    close_words.sort();
+    close_words
+}
+
+// Card names along with the name of each of their faces that has any of the words, for fuzzy
+//  matching whole names - see find_cards_with_close_names. In card name order.
+pub fn get_card_names_with_words(words: &[String]) -> Vec<(String, String)> {
+    let conn = get_db_connection();
+    card_names_with_words(&conn, words)
+}
+
+fn card_names_with_words(conn: &Connection, words: &[String]) -> Vec<(String, String)> {
+    if words.is_empty() {
+        return Vec::new();
+    }
+    let conditions: Vec<String> = (1..=words.len())
+        .map(|i| format!("card_faces.search_name LIKE ?{i}"))
+        .collect();
+    let sql = format!(
+        "SELECT cards.name, card_faces.name FROM card_faces JOIN cards ON cards.id = card_faces.card_id WHERE {} ORDER BY cards.name, face;",
+        conditions.join(" OR ")
+    );
+    let patterns = words
+        .iter()
+        .map(|w| format!("%{}%", normalise_search_text(w)));
+    let mut stmt = conn.prepare(&sql).unwrap();
+    stmt.query_map(params_from_iter(patterns), |row| {
+        Ok((row.get(0)?, row.get(1)?))
+    })
+    .unwrap()
+    .filter_map(|res| res.ok())
+    .collect()
+}
+
+impl fmt::Display for Face {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match &self.mana_cost {
             Some(mc) => write!(f, "{}\t{}", self.name, mc)?,
             None => write!(f, "{}", self.name)?,
         }
         write!(f, "\n{}", self.type_line)?;
-        write!(f, "\n{}", &self.oracle_text)?;
+        write!(
+            f,
+            "\n{}",
+            self.oracle_text.as_deref().unwrap_or("<No Oracle Text>")
+        )?;
 
         if let Some(pt) = &self.power_toughness {
             write!(f, "\n{}", pt)?
@@ -50,26 +153,22 @@ impl fmt::Display for DbCard {
             write!(f, "\nStarting Loyalty: {}", l)?
         }
 
-        if let Some(other_name) = &self.oc_name {
-            write!(f, "\n-----------------")?;
-            write!(f, "\n{}", other_name)?;
-        }
-        if let Some(other_mana_cost) = &self.oc_mana_cost {
-            write!(f, "\t{}", other_mana_cost)?;
-        }
-        if let Some(other_type_line) = &self.oc_type_line {
-            write!(f, "\n{}", other_type_line)?;
-        }
-        if let Some(other_ot) = &self.oc_oracle_text {
-            write!(f, "\n{}", other_ot)?
+        if let Some(d) = &self.defense {
+            write!(f, "\nDefense: {}", d)?
         }
 
-        if let Some(other_pt) = &self.oc_power_toughness {
-            write!(f, "\n{}", other_pt)?
-        }
+        Ok(())
+    }
+}
 
-        if let Some(l) = &self.oc_loyalty {
-            write!(f, "\nStarting Loyalty: {}", l)?
+// unsure if this should be in this file...
+impl fmt::Display for DbCard {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, face) in self.faces.iter().enumerate() {
+            if i > 0 {
+                write!(f, "\n-----------------\n")?;
+            }
+            write!(f, "{}", face)?;
         }
 
         write!(f, "\nFirst Set: {}", self.set_name)?;
@@ -98,58 +197,458 @@ impl PartialEq for DbCard {
 
 impl Eq for DbCard {}
 
+// One face of a card. Most cards only have the one, but split, flip, transform, modal double
+//  faced, adventure and prepare cards (and a few Un- cards) have more - see the layout
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Face {
+    pub name: String,
+    pub mana_cost: Option<String>,
+    pub type_line: String,
+    // None for vanilla cards
+    pub oracle_text: Option<String>,
+    pub power_toughness: Option<String>,
+    pub loyalty: Option<String>,
+    pub defense: Option<String>,
+}
+
 #[derive(Debug, Default)]
 pub struct DbCard {
     pub scryfall_uuid: [u8; 16],
     pub oracle_uuid: [u8; 16],
+    // The front face's name for cards with more than one face
     pub name: String,
-    pub type_line: String,
-    pub oracle_text: String,
-    pub power_toughness: Option<String>,
-    pub loyalty: Option<String>,
-    pub mana_cost: Option<String>,
+    // Scryfall's layout, like normal, split, transform or adventure
+    pub layout: String,
+    // In printed order. Always at least one
+    pub faces: Vec<Face>,
     pub scryfall_uri: Option<String>,
-    pub oc_name: Option<String>,
-    pub oc_type_line: Option<String>,
-    pub oc_oracle_text: Option<String>,
-    pub oc_power_toughness: Option<String>,
-    pub oc_loyalty: Option<String>,
-    pub oc_mana_cost: Option<String>,
     pub set_name: String,
     pub released_at: String,
+    pub edhrec_rank: Option<u64>,
+}
+
+impl DbCard {
+    // Every face's name, like Scryfall does it - "Fire // Ice"
+    pub fn full_name(&self) -> String {
+        if self.faces.len() < 2 {
+            return self.name.clone();
+        }
+        self.faces
+            .iter()
+            .map(|face| face.name.as_str())
+            .collect::<Vec<&str>>()
+            .join(" // ")
+    }
+}
+
+const DB_CARD_COLUMNS: &str = "id, scryfall_uuid, oracle_uuid, name, layout, scryfall_uri, set_name, released_at, edhrec_rank";
+
+// A single printing of a card. The oracle level stuff (text, types, etc.) lives in DbCard
+#[derive(Debug, Default, PartialEq)]
+pub struct DbPrint {
+    pub set_code: String,
+    pub set_name: String,
+    pub collector_number: String,
+    pub rarity: String,
+    pub artist: Option<String>,
+    pub released_at: String,
+    pub scryfall_uri: String,
+    pub usd: Option<f64>,
+    pub usd_foil: Option<f64>,
+    pub usd_etched: Option<f64>,
+    pub eur: Option<f64>,
+    pub eur_foil: Option<f64>,
+    pub tix: Option<f64>,
+    pub flavour_text: Option<String>,
+    pub flavour_name: Option<String>,
+    pub watermark: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Currency {
+    Usd,
+    Eur,
+    Tix,
+}
+
+impl Currency {
+    pub const ALL: [Currency; 3] = [Currency::Usd, Currency::Eur, Currency::Tix];
+
+    pub fn format(&self, price: f64) -> String {
+        match self {
+            Currency::Usd => format!("${:.2}", price),
+            Currency::Eur => format!("€{:.2}", price),
+            Currency::Tix => format!("{:.2} tix", price),
+        }
+    }
+}
+
+impl DbPrint {
+    // Cheapest of the finishes (normal, foil, etched) the print has a price for
+    pub fn price(&self, currency: Currency) -> Option<f64> {
+        let prices = match currency {
+            Currency::Usd => vec![self.usd, self.usd_foil, self.usd_etched],
+            Currency::Eur => vec![self.eur, self.eur_foil],
+            Currency::Tix => vec![self.tix],
+        };
+        prices.into_iter().flatten().reduce(f64::min)
+    }
+}
+
+impl fmt::Display for DbPrint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}\t{} ({}) #{}\t{}",
+            self.released_at,
+            self.set_name,
+            self.set_code.to_uppercase(),
+            self.collector_number,
+            self.rarity
+        )?;
+        if let Some(flavour_name) = &self.flavour_name {
+            write!(f, "\t\"{}\"", flavour_name)?;
+        }
+        if let Some(artist) = &self.artist {
+            write!(f, "\t{}", artist)?;
+        }
+        for currency in Currency::ALL {
+            if let Some(price) = self.price(currency) {
+                write!(f, "\t{}", currency.format(price))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Default, PartialEq)]
+pub struct DbSet {
+    pub code: String,
+    pub name: String,
+    pub set_type: String,
+    pub released_at: String,
+    pub card_count: u32,
+}
+
+impl fmt::Display for DbSet {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}\t{}\t{}\t{}\t{} cards",
+            self.released_at,
+            self.code.to_uppercase(),
+            self.name,
+            self.set_type,
+            self.card_count
+        )
+    }
+}
+
+const DB_PRINT_COLUMNS: &str = "set_code, set_name, collector_number, rarity, artist, released_at, scryfall_uri, usd, usd_foil, usd_etched, eur, eur_foil, tix, flavour_text, flavour_name, watermark";
+
+// Must line up with the order of DB_PRINT_COLUMNS
+fn row_to_db_print(row: &Row) -> rusqlite::Result<DbPrint> {
+    Ok(DbPrint {
+        set_code: row.get(0)?,
+        set_name: row.get(1)?,
+        collector_number: row.get(2)?,
+        rarity: row.get(3)?,
+        artist: row.get(4)?,
+        released_at: row.get(5)?,
+        scryfall_uri: row.get(6)?,
+        usd: row.get(7)?,
+        usd_foil: row.get(8)?,
+        usd_etched: row.get(9)?,
+        eur: row.get(10)?,
+        eur_foil: row.get(11)?,
+        tix: row.get(12)?,
+        flavour_text: row.get(13)?,
+        flavour_name: row.get(14)?,
+        watermark: row.get(15)?,
+    })
+}
+
+// Must line up with the order of DB_CARD_COLUMNS. The faces come from card_faces.
+fn row_to_db_card(conn: &Connection, row: &Row) -> rusqlite::Result<DbCard> {
+    let card_id: i64 = row.get(0)?;
+    Ok(DbCard {
+        scryfall_uuid: row.get(1)?,
+        oracle_uuid: row.get(2)?,
+        name: row.get(3)?,
+        layout: row.get(4)?,
+        faces: card_faces(conn, card_id)?,
+        scryfall_uri: row.get(5)?,
+        set_name: row.get(6)?,
+        released_at: row.get(7)?,
+        edhrec_rank: row.get(8)?,
+    })
+}
+
+fn card_faces(conn: &Connection, card_id: i64) -> rusqlite::Result<Vec<Face>> {
+    let mut stmt = conn.prepare_cached(
+        "SELECT name, mana_cost, type_line, oracle_text, power_toughness, loyalty, defense FROM card_faces WHERE card_id = ?1 ORDER BY face",
+    )?;
+    stmt.query_map([card_id], |row| {
+        Ok(Face {
+            name: row.get(0)?,
+            mana_cost: row.get(1)?,
+            type_line: row.get(2)?,
+            oracle_text: row.get(3)?,
+            power_toughness: row.get(4)?,
+            loyalty: row.get(5)?,
+            defense: row.get(6)?,
+        })
+    })?
+    .collect()
 }
 
 pub fn get_card_by_name(name: &str) -> Option<DbCard> {
     let sqlite_file = get_local_data_sqlite_file();
     let conn = Connection::open(sqlite_file).unwrap();
-    let sql = "SELECT scryfall_uuid, oracle_uuid, name, type_line, oracle_text, power_toughness, loyalty, mana_cost, scryfall_uri, oc_name, oc_type_line, oc_oracle_text, oc_power_toughness, oc_loyalty, oc_mana_cost, set_name, released_at
-             FROM cards WHERE name = (?1)";
-    let mut stmt = conn.prepare(sql).unwrap();
-    let mut rows = stmt.query([name]).unwrap();
-    rows.next().unwrap().map(|row| DbCard {
-        scryfall_uuid: row.get(0).unwrap(),
-        oracle_uuid: row.get(1).unwrap(),
-        name: row.get(2).unwrap(),
-        type_line: row.get(3).unwrap(),
-        oracle_text: row.get(4).unwrap(),
-        power_toughness: row.get(5).unwrap(),
-        loyalty: row.get(6).unwrap(),
-        mana_cost: row.get(7).unwrap(),
-        scryfall_uri: row.get(8).unwrap(),
-        oc_name: row.get(9).unwrap(),
-        oc_type_line: row.get(10).unwrap(),
-        oc_oracle_text: row.get(11).unwrap(),
-        oc_power_toughness: row.get(12).unwrap(),
-        oc_loyalty: row.get(13).unwrap(),
-        oc_mana_cost: row.get(14).unwrap(),
-        set_name: row.get(15).unwrap(),
-        released_at: row.get(16).unwrap(),
+    card_by_name(&conn, name)
+}
+
+// Also finds cards by other names they've been printed with
+fn card_by_name(conn: &Connection, name: &str) -> Option<DbCard> {
+    let sql = format!(
+        "SELECT {} FROM cards WHERE name = (?1) OR search_name = (?2) OR id IN (SELECT card_id FROM card_aliases WHERE search_alias = (?2)) ORDER BY name = (?1) DESC, search_name = (?2) DESC",
+        DB_CARD_COLUMNS
+    );
+    let mut stmt = conn.prepare(&sql).unwrap();
+    let mut rows = stmt.query([name, &normalise_search_text(name)]).unwrap();
+    rows.next()
+        .unwrap()
+        .map(|row| row_to_db_card(conn, row).unwrap())
+}
+
+// The cards a nickname could mean, like "bob" for Dark Confidant
+pub fn get_cards_for_nickname(nickname: &str) -> Vec<DbCard> {
+    let conn = get_db_connection();
+    cards_for_nickname(&conn, nickname)
+}
+
+fn cards_for_nickname(conn: &Connection, nickname: &str) -> Vec<DbCard> {
+    let sql = format!(
+        "SELECT {} FROM cards WHERE name IN (SELECT card_name FROM nicknames WHERE nickname = ?1 AND NOT removed) ORDER BY name",
+        DB_CARD_COLUMNS
+    );
+    let mut stmt = conn.prepare(&sql).unwrap();
+    stmt.query_map([normalise_search_text(nickname)], |row| {
+        row_to_db_card(conn, row)
     })
+    .unwrap()
+    .filter_map(|res| res.ok())
+    .collect()
+}
+
+// Up to limit card names with a word starting with each word of the prefix, so "lig bo" finds
+//  Lightning Bolt. Names starting with the first word come first, then the most played.
+pub fn get_card_name_completions(prefix: &str, limit: usize) -> Vec<String> {
+    let conn = get_db_connection();
+    card_name_completions(&conn, prefix, limit)
+}
+
+fn card_name_completions(conn: &Connection, prefix: &str, limit: usize) -> Vec<String> {
+    let prefix = normalise_search_text(prefix);
+    let words: Vec<&str> = prefix.split_whitespace().collect();
+    if words.is_empty() {
+        return Vec::new();
+    }
+    // Everything starting with the word sorts between the word and the word followed by the
+    //  biggest character there is, so the index can be used
+    let mut params: Vec<Value> = vec![(limit as i64).into()];
+    let mut word_range = |word: &str| {
+        params.push(word.to_string().into());
+        params.push(format!("{}\u{10FFFF}", word).into());
+        format!("word >= ?{} AND word < ?{}", params.len() - 1, params.len())
+    };
+    let first_word = word_range(words[0]);
+    let other_words: Vec<String> = words[1..]
+        .iter()
+        .map(|word| {
+            format!(
+                " AND cards.id IN (SELECT card_id FROM card_name_words WHERE {})",
+                word_range(word)
+            )
+        })
+        .collect();
+    let sql = format!(
+        "SELECT cards.name FROM card_name_words JOIN cards ON cards.id = card_name_words.card_id
+         WHERE {}{}
+         GROUP BY cards.id
+         ORDER BY MIN(card_name_words.position), cards.edhrec_rank NULLS LAST, cards.name
+         LIMIT ?1;",
+        first_word,
+        other_words.concat()
+    );
+    let mut stmt = conn.prepare(&sql).unwrap();
+    stmt.query_map(params_from_iter(params), |row| row.get(0))
+        .unwrap()
+        .filter_map(|res| res.ok())
+        .collect()
+}
+
+// Cards whose name has these initials (see name_initialisms), most popular first
+pub fn get_cards_for_initialism(initialism: &str) -> Vec<DbCard> {
+    let conn = get_db_connection();
+    cards_for_initialism(&conn, initialism)
+}
+
+fn cards_for_initialism(conn: &Connection, initialism: &str) -> Vec<DbCard> {
+    let sql = format!(
+        "SELECT {} FROM cards WHERE id IN (SELECT card_id FROM card_initialisms WHERE initialism = ?1) ORDER BY edhrec_rank NULLS LAST, name",
+        DB_CARD_COLUMNS
+    );
+    // Normalised like the names they were made from, so "J.V.P." works too
+    let initialism: String = normalise_search_text(initialism)
+        .split_whitespace()
+        .collect();
+    let mut stmt = conn.prepare(&sql).unwrap();
+    stmt.query_map([initialism], |row| row_to_db_card(conn, row))
+        .unwrap()
+        .filter_map(|res| res.ok())
+        .collect()
+}
+
+// Every nickname and the card it means, in nickname order
+pub fn get_all_nicknames() -> Vec<(String, String)> {
+    let conn = get_db_connection();
+    all_nicknames(&conn)
+}
+
+fn all_nicknames(conn: &Connection) -> Vec<(String, String)> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT nickname, card_name FROM nicknames WHERE NOT removed ORDER BY nickname, card_name;",
+        )
+        .unwrap();
+    stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+        .unwrap()
+        .filter_map(|res| res.ok())
+        .collect()
+}
+
+// Returns the card's proper name. The card name can be in any case, or another name it's been
+//  printed with.
+pub fn add_nickname(nickname: &str, card_name: &str) -> Result<String, NicknameError> {
+    let conn = get_db_connection();
+    insert_user_nickname(&conn, nickname, card_name)
+}
+
+fn insert_user_nickname(
+    conn: &Connection,
+    nickname: &str,
+    card_name: &str,
+) -> Result<String, NicknameError> {
+    let Some(card) = card_by_name(conn, card_name.trim()) else {
+        return Err(NicknameError::CardNotFound(card_name.to_string()));
+    };
+    let name = card.name;
+    // Adding a bundled nickname again makes it the user's, so it survives init_db
+    conn.execute(
+        "INSERT INTO nicknames (nickname, card_name, user_added) VALUES (?1, ?2, 1)
+         ON CONFLICT (nickname, card_name) DO UPDATE SET user_added = 1, removed = 0;",
+        params![normalise_search_text(nickname), name],
+    )
+    .unwrap();
+    Ok(name)
+}
+
+// Without a card name, every card for the nickname goes. Returns how many were removed. Removed
+//  nicknames are only marked as removed, so bundled ones don't come back after an update.
+pub fn remove_nickname(nickname: &str, card_name: Option<&str>) -> usize {
+    let conn = get_db_connection();
+    delete_nickname(&conn, nickname, card_name)
+}
+
+fn delete_nickname(conn: &Connection, nickname: &str, card_name: Option<&str>) -> usize {
+    let nickname = normalise_search_text(nickname);
+    let Some(card_name) = card_name else {
+        return conn
+            .execute(
+                "UPDATE nicknames SET removed = 1 WHERE nickname = ?1 AND NOT removed;",
+                [nickname],
+            )
+            .unwrap();
+    };
+    // Card names are stored as they're printed, so have to be normalised here to compare
+    let card_name = normalise_search_text(card_name);
+    let mut stmt = conn
+        .prepare("SELECT card_name FROM nicknames WHERE nickname = ?1 AND NOT removed;")
+        .unwrap();
+    let matching: Vec<String> = stmt
+        .query_map([&nickname], |row| row.get(0))
+        .unwrap()
+        .filter_map(|res: rusqlite::Result<String>| res.ok())
+        .filter(|name| normalise_search_text(name) == card_name)
+        .collect();
+    for name in &matching {
+        conn.execute(
+            "UPDATE nicknames SET removed = 1 WHERE nickname = ?1 AND card_name = ?2;",
+            params![nickname, name],
+        )
+        .unwrap();
+    }
+    matching.len()
+}
+
+// The nicknames added with add_nickname and the ones removed with remove_nickname, so init_db
+//  can put them back after the bundled ones
+#[derive(Default)]
+struct KeptNicknames {
+    added: Vec<(String, String)>,
+    removed: Vec<(String, String)>,
+}
+
+fn kept_nicknames(conn: &Connection) -> KeptNicknames {
+    KeptNicknames {
+        added: nicknames_where(conn, "user_added = 1"),
+        removed: nicknames_where(conn, "removed = 1"),
+    }
+}
+
+fn restore_nicknames(conn: &Connection, kept: &KeptNicknames) {
+    insert_nicknames(conn, &kept.added, true);
+    for (nickname, card_name) in &kept.removed {
+        conn.execute(
+            "UPDATE nicknames SET removed = 1 WHERE nickname = ?1 AND card_name = ?2;",
+            params![nickname, card_name],
+        )
+        .unwrap();
+    }
+}
+
+// Old databases might not have a nicknames table (or the removed column), which means there's
+//  nothing to keep
+fn nicknames_where(conn: &Connection, condition: &str) -> Vec<(String, String)> {
+    let sql = format!(
+        "SELECT nickname, card_name FROM nicknames WHERE {};",
+        condition
+    );
+    let Ok(mut stmt) = conn.prepare(&sql) else {
+        return Vec::new();
+    };
+    stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+        .unwrap()
+        .filter_map(|res| res.ok())
+        .collect()
+}
+
+fn insert_nicknames(conn: &Connection, nicknames: &[(String, String)], user_added: bool) {
+    for (nickname, card_name) in nicknames {
+        conn.execute(
+            "INSERT INTO nicknames (nickname, card_name, user_added) VALUES (?1, ?2, ?3)
+             ON CONFLICT (nickname, card_name) DO UPDATE SET user_added = MAX(user_added, ?3);",
+            params![nickname, card_name, user_added],
+        )
+        .unwrap();
+    }
 }
 
 pub fn percentage_search_strings(search_strings: &[String]) -> Vec<String> {
     let mut percentaged_search_strings = Vec::new();
-    for mut search_string in search_strings.iter().cloned() {
+    for search_string in search_strings {
+        let mut search_string = normalise_search_text(search_string);
         search_string.push('%');
         search_string.insert(0, '%');
         percentaged_search_strings.push(search_string);
@@ -162,11 +661,10 @@ pub fn find_matching_cards_scryfall_style(percentaged_search_strings: &[String])
     let sqlite_file = get_local_data_sqlite_file();
     let conn = Connection::open(sqlite_file).unwrap();
 
-    let mut sql: String = "SELECT scryfall_uuid, oracle_uuid, name, type_line, oracle_text, power_toughness, loyalty, mana_cost, scryfall_uri, oc_name, oc_type_line, oc_oracle_text, oc_power_toughness, oc_loyalty, oc_mana_cost, set_name, released_at FROM cards WHERE".into();
+    let mut sql: String = format!("SELECT {} FROM cards WHERE", DB_CARD_COLUMNS);
     for i in 0..percentaged_search_strings.len() {
         sql.push_str(&format!(
-            " ( LOWER(name) LIKE (?{}) OR LOWER(oc_name) LIKE (?{}) ) AND",
-            i + 1,
+            " id IN (SELECT card_id FROM card_faces WHERE search_name LIKE (?{})) AND",
             i + 1
         ));
     }
@@ -177,105 +675,194 @@ pub fn find_matching_cards_scryfall_style(percentaged_search_strings: &[String])
     sql.pop();
     let mut stmt = conn.prepare(&sql).unwrap();
     stmt.query_map(params_from_iter(percentaged_search_strings), |row| {
-        Ok(DbCard {
-            scryfall_uuid: row.get(0).unwrap(),
-            oracle_uuid: row.get(1).unwrap(),
-            name: row.get(2).unwrap(),
-            type_line: row.get(3).unwrap(),
-            oracle_text: row.get(4).unwrap(),
-            power_toughness: row.get(5).unwrap(),
-            loyalty: row.get(6).unwrap(),
-            mana_cost: row.get(7).unwrap(),
-            scryfall_uri: row.get(8).unwrap(),
-            oc_name: row.get(9).unwrap(),
-            oc_type_line: row.get(10).unwrap(),
-            oc_oracle_text: row.get(11).unwrap(),
-            oc_power_toughness: row.get(12).unwrap(),
-            oc_loyalty: row.get(13).unwrap(),
-            oc_mana_cost: row.get(14).unwrap(),
-            set_name: row.get(15).unwrap(),
-            released_at: row.get(16).unwrap(),
-        })
+        row_to_db_card(&conn, row)
     })
     .unwrap()
     .filter_map(|res| res.ok())
     .collect()
 }
 
+pub fn find_cards_matching_query(query: &Query) -> Result<Vec<DbCard>, QueryError> {
+    let conn = get_db_connection();
+    query_cards(&conn, query)
+}
+
+fn query_cards(conn: &Connection, query: &Query) -> Result<Vec<DbCard>, QueryError> {
+    let filter = compile_query(query)?;
+    let sql = format!(
+        "SELECT {} FROM cards WHERE {} ORDER BY {}",
+        DB_CARD_COLUMNS, filter.sql, filter.order_by
+    );
+    let mut stmt = conn.prepare(&sql).unwrap();
+    Ok(stmt
+        .query_map(params_from_iter(filter.params), |row| {
+            row_to_db_card(conn, row)
+        })
+        .unwrap()
+        .filter_map(|res| res.ok())
+        .collect())
+}
+
+// Cheaper than find_cards_matching_query when all that matters is whether anything matches
+pub fn any_cards_match_query(query: &Query) -> Result<bool, QueryError> {
+    let conn = get_db_connection();
+    query_has_cards(&conn, query)
+}
+
+fn query_has_cards(conn: &Connection, query: &Query) -> Result<bool, QueryError> {
+    let filter = compile_query(query)?;
+    let sql = format!("SELECT EXISTS (SELECT 1 FROM cards WHERE {})", filter.sql);
+    Ok(conn
+        .query_row(&sql, params_from_iter(filter.params), |row| row.get(0))
+        .unwrap())
+}
+
+// Best matches first. See fts_expression for what the search text can look like.
+pub fn find_cards_by_oracle_text(search_text: &str) -> Vec<DbCard> {
+    let conn = get_db_connection();
+    search_oracle_text(&conn, search_text)
+}
+
+fn search_oracle_text(conn: &Connection, search_text: &str) -> Vec<DbCard> {
+    let Some(expression) = fts_expression(search_text) else {
+        return Vec::new();
+    };
+    let sql = format!(
+        "SELECT {} FROM cards
+         JOIN (SELECT rowid AS fts_id, bm25(oracle_fts) AS score FROM oracle_fts WHERE oracle_fts MATCH (?1))
+           ON id = fts_id
+         ORDER BY score",
+        DB_CARD_COLUMNS
+    );
+    let mut stmt = conn.prepare(&sql).unwrap();
+    stmt.query_map([expression], |row| row_to_db_card(conn, row))
+        .unwrap()
+        .filter_map(|res| res.ok())
+        .collect()
+}
+
+// Printed and flavour names, not including the card's actual name
 pub fn get_all_names_for_card(card: &DbCard) -> Vec<String> {
-    let sqlite_file = get_local_data_sqlite_file();
-    let conn = Connection::open(sqlite_file).unwrap();
+    let conn = get_db_connection();
+    card_aliases(&conn, &card.name)
+}
+
+fn card_aliases(conn: &Connection, name: &str) -> Vec<String> {
     let mut stmt = conn
         .prepare(
-            "SELECT scryfall_uuid, oracle_uuid, name,  type_line, oracle_text, power_toughness, loyalty, mana_cost, scryfall_uri, oc_name, oc_type_line, oc_oracle_text, oc_power_toughness, oc_loyalty, oc_mana_cost, set_name, released_at FROM cards WHERE oracle_uuid = (?1) ",
+            "SELECT alias FROM card_aliases WHERE card_id = (SELECT id FROM cards WHERE name = ?1) ORDER BY alias;",
         )
         .unwrap();
-    let cards: Vec<DbCard> = stmt
-        .query_map([card.oracle_uuid], |row| {
-            Ok(DbCard {
-                scryfall_uuid: row.get(0).unwrap(),
-                oracle_uuid: row.get(1).unwrap(),
-                name: row.get(2).unwrap(),
-                type_line: row.get(3).unwrap(),
-                oracle_text: row.get(4).unwrap(),
-                power_toughness: row.get(5).unwrap(),
-                loyalty: row.get(6).unwrap(),
-                mana_cost: row.get(7).unwrap(),
-                scryfall_uri: row.get(8).unwrap(),
-                oc_name: row.get(9).unwrap(),
-                oc_type_line: row.get(10).unwrap(),
-                oc_oracle_text: row.get(11).unwrap(),
-                oc_power_toughness: row.get(12).unwrap(),
-                oc_loyalty: row.get(13).unwrap(),
-                oc_mana_cost: row.get(14).unwrap(),
-                set_name: row.get(15).unwrap(),
-                released_at: row.get(16).unwrap(),
-            })
-        })
+    stmt.query_map([name], |row| row.get(0))
         .unwrap()
         .filter_map(|res| res.ok())
-        .collect();
+        .collect()
+}
 
-    let mut card_names: Vec<String> = Vec::new();
-    for card in cards {
-        // No double faced cards with 2 names... so far... this will need to be fixed when that happens
-        card_names.push(card.name.clone());
-    }
-    card_names
+// Oldest first
+pub fn get_prints_for_card(card: &DbCard) -> Vec<DbPrint> {
+    let conn = get_db_connection();
+    card_prints(&conn, &card.name)
 }
 
-pub fn find_matching_cards(name: &str) -> Vec<DbCard> {
-    let sqlite_file = get_local_data_sqlite_file();
-    let conn = Connection::open(sqlite_file).unwrap();
-    // There must be something better than this - although I don't think it's possible with a str
-    let mut name = name.to_string();
-    name.push('%');
-    name.insert(0, '%');
+fn card_prints(conn: &Connection, name: &str) -> Vec<DbPrint> {
+    let sql = format!(
+        "SELECT {} FROM prints WHERE card_id = (SELECT id FROM cards WHERE name = ?1) ORDER BY released_at, set_code, collector_number;",
+        DB_PRINT_COLUMNS
+    );
+    let mut stmt = conn.prepare(&sql).unwrap();
+    stmt.query_map([name], row_to_db_print)
+        .unwrap()
+        .filter_map(|res| res.ok())
+        .collect()
+}
+
+// Every print illustrated by the artist (or anyone with the search in their name), oldest first,
+//  along with the name of the card
+pub fn get_prints_by_artist(artist: &str) -> Vec<(String, DbPrint)> {
+    let conn = get_db_connection();
+    artist_prints(&conn, artist)
+}
+
+fn artist_prints(conn: &Connection, artist: &str) -> Vec<(String, DbPrint)> {
+    let sql = format!(
+        "SELECT {}, (SELECT name FROM cards WHERE cards.id = prints.card_id) FROM prints WHERE LOWER(artist) LIKE ?1 ESCAPE '\\' ORDER BY released_at, set_code, collector_number;",
+        DB_PRINT_COLUMNS
+    );
+    let mut stmt = conn.prepare(&sql).unwrap();
+    stmt.query_map([like_pattern(artist)], |row| {
+        Ok((row.get(16)?, row_to_db_print(row)?))
+    })
+    .unwrap()
+    .filter_map(|res| res.ok())
+    .collect()
+}
+
+// None if no print of the card has a price in that currency
+pub fn get_cheapest_print_for_card(card: &DbCard, currency: Currency) -> Option<(DbPrint, f64)> {
+    let conn = get_db_connection();
+    cheapest_print(card_prints(&conn, &card.name), currency)
+}
+
+fn cheapest_print(prints: Vec<DbPrint>, currency: Currency) -> Option<(DbPrint, f64)> {
+    prints
+        .into_iter()
+        .filter_map(|print| print.price(currency).map(|price| (print, price)))
+        .min_by(|(_, a), (_, b)| a.total_cmp(b))
+}
+
+// The way decklists and collection exports refer to a card, e.g. DMU 107
+pub fn get_print_by_set_and_number(
+    set_code: &str,
+    collector_number: &str,
+) -> Option<(DbCard, DbPrint)> {
+    let conn = get_db_connection();
+    print_by_set_and_number(&conn, set_code, collector_number)
+}
+
+fn print_by_set_and_number(
+    conn: &Connection,
+    set_code: &str,
+    collector_number: &str,
+) -> Option<(DbCard, DbPrint)> {
+    // card_id goes last so the print columns line up for row_to_db_print
+    let sql = format!(
+        "SELECT {}, card_id FROM prints WHERE set_code = ?1 AND LOWER(collector_number) = ?2;",
+        DB_PRINT_COLUMNS
+    );
+    let (print, card_id): (DbPrint, i64) = conn
+        .query_row(
+            &sql,
+            [set_code.to_lowercase(), collector_number.to_lowercase()],
+            |row| Ok((row_to_db_print(row)?, row.get(16)?)),
+        )
+        .ok()?;
+    let sql = format!("SELECT {} FROM cards WHERE id = ?1", DB_CARD_COLUMNS);
+    let card = conn
+        .query_row(&sql, [card_id], |row| row_to_db_card(conn, row))
+        .ok()?;
+    Some((card, print))
+}
+
+// Oldest first
+pub fn get_all_sets() -> Vec<DbSet> {
+    let conn = get_db_connection();
+    all_sets(&conn)
+}
+
+fn all_sets(conn: &Connection) -> Vec<DbSet> {
     let mut stmt = conn
         .prepare(
-            "SELECT scryfall_uuid, oracle_uuid, name,  type_line, oracle_text, power_toughness, loyalty, mana_cost, scryfall_uri, oc_name, oc_type_line, oc_oracle_text, oc_power_toughness, oc_loyalty, oc_mana_cost, set_name, released_at
-             FROM cards WHERE LOWER(name) LIKE (?1) OR LOWER(oc_name) LIKE (?1)",
+            "SELECT code, name, set_type, released_at, card_count FROM sets ORDER BY released_at, code;",
         )
         .unwrap();
-    stmt.query_map([name], |row| {
-        Ok(DbCard {
-            scryfall_uuid: row.get(0).unwrap(),
-            oracle_uuid: row.get(1).unwrap(),
-            name: row.get(2).unwrap(),
-            type_line: row.get(3).unwrap(),
-            oracle_text: row.get(4).unwrap(),
-            power_toughness: row.get(5).unwrap(),
-            loyalty: row.get(6).unwrap(),
-            mana_cost: row.get(7).unwrap(),
-            scryfall_uri: row.get(8).unwrap(),
-            oc_name: row.get(9).unwrap(),
-            oc_type_line: row.get(10).unwrap(),
-            oc_oracle_text: row.get(11).unwrap(),
-            oc_power_toughness: row.get(12).unwrap(),
-            oc_loyalty: row.get(13).unwrap(),
-            oc_mana_cost: row.get(14).unwrap(),
-            set_name: row.get(15).unwrap(),
-            released_at: row.get(16).unwrap(),
+    stmt.query_map([], |row| {
+        Ok(DbSet {
+            code: row.get(0)?,
+            name: row.get(1)?,
+            set_type: row.get(2)?,
+            released_at: row.get(3)?,
+            card_count: row.get(4)?,
         })
     })
     .unwrap()
@@ -283,21 +870,101 @@ pub fn find_matching_cards(name: &str) -> Vec<DbCard> {
     .collect()
 }
 
-pub enum DbExistanceErrors {
-    DbFileDoesntExist,
-    DbFileIsEmptyOfCards,
-    DbFileIsEmptyOfWords,
+// Alphabetical
+pub fn get_keywords_for_card(card: &DbCard) -> Vec<String> {
+    let conn = get_db_connection();
+    card_keywords(&conn, &card.name)
 }
 
-pub fn check_db_exists_and_populated() -> Result<(), DbExistanceErrors> {
-    let sqlite_file = get_local_data_sqlite_file();
-    if !sqlite_file.exists() {
-        return Err(DbExistanceErrors::DbFileDoesntExist);
-    }
-    let conn = Connection::open(sqlite_file).unwrap();
-    let mut words_stmt = conn.prepare("SELECT COUNT(*) FROM mtg_words;").unwrap();
-    let mut rows = words_stmt.query([]).unwrap();
-    match rows.next().unwrap() {
+fn card_keywords(conn: &Connection, name: &str) -> Vec<String> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT keywords.name FROM keywords JOIN card_keywords ON card_keywords.keyword_id = keywords.id WHERE card_keywords.card_id = (SELECT id FROM cards WHERE name = ?1) ORDER BY keywords.name;",
+        )
+        .unwrap();
+    stmt.query_map([name], |row| row.get(0))
+        .unwrap()
+        .filter_map(|res| res.ok())
+        .collect()
+}
+
+// Every keyword with the number of cards that have it, most common first
+pub fn get_all_keywords() -> Vec<(String, u32)> {
+    let conn = get_db_connection();
+    all_keywords(&conn)
+}
+
+fn all_keywords(conn: &Connection) -> Vec<(String, u32)> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT keywords.name, COUNT(card_keywords.card_id) AS card_count FROM keywords JOIN card_keywords ON card_keywords.keyword_id = keywords.id GROUP BY keywords.id ORDER BY card_count DESC, keywords.name;",
+        )
+        .unwrap();
+    stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+        .unwrap()
+        .filter_map(|res| res.ok())
+        .collect()
+}
+
+// Every format Scryfall knows about, in the order of FORMAT_NAMES
+pub fn get_legalities_for_card(card: &DbCard) -> Vec<(String, Legality)> {
+    let conn = get_db_connection();
+    card_legalities(&conn, &card.name)
+}
+
+fn card_legalities(conn: &Connection, name: &str) -> Vec<(String, Legality)> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT format, legality FROM legalities WHERE card_id = (SELECT id FROM cards WHERE name = ?1) ORDER BY rowid;",
+        )
+        .unwrap();
+    stmt.query_map([name], |row| {
+        Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+    })
+    .unwrap()
+    .filter_map(|res| res.ok())
+    .filter_map(|(format, legality)| Legality::from_db_str(&legality).map(|l| (format, l)))
+    .collect()
+}
+
+pub fn find_matching_cards(name: &str) -> Vec<DbCard> {
+    let sqlite_file = get_local_data_sqlite_file();
+    let conn = Connection::open(sqlite_file).unwrap();
+    // There must be something better than this - although I don't think it's possible with a str
+    let mut name = normalise_search_text(name);
+    name.push('%');
+    name.insert(0, '%');
+    let sql = format!(
+        "SELECT {} FROM cards WHERE id IN (SELECT card_id FROM card_faces WHERE search_name LIKE (?1))",
+        DB_CARD_COLUMNS
+    );
+    let mut stmt = conn.prepare(&sql).unwrap();
+    stmt.query_map([name], |row| row_to_db_card(&conn, row))
+        .unwrap()
+        .filter_map(|res| res.ok())
+        .collect()
+}
+
+pub enum DbExistanceErrors {
+    DbFileDoesntExist,
+    // Made by an older version with different tables - needs another --update
+    DbFileIsOutOfDate,
+    DbFileIsEmptyOfCards,
+    DbFileIsEmptyOfWords,
+}
+
+pub fn check_db_exists_and_populated() -> Result<(), DbExistanceErrors> {
+    let sqlite_file = get_local_data_sqlite_file();
+    if !sqlite_file.exists() {
+        return Err(DbExistanceErrors::DbFileDoesntExist);
+    }
+    let conn = Connection::open(sqlite_file).unwrap();
+    if !schema_is_current(&conn) {
+        return Err(DbExistanceErrors::DbFileIsOutOfDate);
+    }
+    let mut words_stmt = conn.prepare("SELECT COUNT(*) FROM mtg_words;").unwrap();
+    let mut rows = words_stmt.query([]).unwrap();
+    match rows.next().unwrap() {
         Some(_count) => (),
         None => return Err(DbExistanceErrors::DbFileIsEmptyOfWords),
     }
@@ -312,249 +979,819 @@ pub fn check_db_exists_and_populated() -> Result<(), DbExistanceErrors> {
     Ok(())
 }
 
+// Goes up by one whenever the tables change, so a database from an older version is noticed
+//  before a search fails on it. Databases from before this was added are 0.
+const SCHEMA_VERSION: i64 = 2;
+
+fn schema_is_current(conn: &Connection) -> bool {
+    conn.query_row("PRAGMA user_version;", [], |row| row.get::<_, i64>(0))
+        .is_ok_and(|version| version == SCHEMA_VERSION)
+}
+
 const CREATE_CARDS_TABLE_SQL: &str = "
 CREATE TABLE cards (
+    id INTEGER PRIMARY KEY,
     scryfall_uuid BLOB NOT NULL UNIQUE,
     oracle_uuid BLOG NOT NULL,
     name TEXT NOT NULL UNIQUE,
-    type_line TEXT,
+    search_name TEXT NOT NULL,
+    scryfall_uri TEXT UNIQUE,
+    set_name TEXT NOT NULL,
+    released_at TEXT NOT NULL,
+    colours INTEGER,
+    colour_identity INTEGER,
+    produced_mana INTEGER,
+    mana_value REAL,
+    layout TEXT NOT NULL,
+    reserved INTEGER,
+    game_changer INTEGER,
+    edhrec_rank INTEGER,
+    penny_rank INTEGER
+)";
+// Colours are stored as bits - see the colour module. A card's colours are every colour on any
+//  of its faces. The name is the front face's name for cards with more than one face - everything else about the faces is in card_faces
+
+// Every face of every card, in printed order from 0. Single faced cards have the one face here
+//  too. A face's colours can differ from the card's (like the back of a transform card).
+const CREATE_CARD_FACES_TABLE_SQL: &str = "
+CREATE TABLE card_faces (
+    card_id INTEGER NOT NULL REFERENCES cards(id),
+    face INTEGER NOT NULL,
+    name TEXT NOT NULL,
+    search_name TEXT NOT NULL,
+    mana_cost TEXT,
+    type_line TEXT NOT NULL,
     oracle_text TEXT,
     power_toughness TEXT,
     loyalty TEXT,
-    mana_cost TEXT,
-    scryfall_uri TEXT UNIQUE,
-    oc_name TEXT DEFAULT NULL,
-    oc_type_line TEXT DEFAULT NULL,
-    oc_oracle_text TEXT DEFAULT NULL,
-    oc_power_toughness TEXT DEFAULT NULL,
-    oc_loyalty TEXT DEAFULT NULL,
-    oc_mana_cost TEXT DEAFULT NULL,
+    defense TEXT,
+    colours INTEGER,
+    colour_indicator INTEGER,
+    PRIMARY KEY (card_id, face)
+)";
+
+// rowid is the id of the card in the cards table. Every face's oracle text goes in together.
+const CREATE_ORACLE_FTS_TABLE_SQL: &str = "
+CREATE VIRTUAL TABLE oracle_fts USING fts5(
+    oracle_text,
+    tokenize = 'unicode61 remove_diacritics 2'
+)";
+
+// face is the face's position in card_faces. kind is one of supertype, type, or
+//  subtype - see TypeKind
+const CREATE_CARD_TYPES_TABLE_SQL: &str = "
+CREATE TABLE card_types (
+    card_id INTEGER NOT NULL REFERENCES cards(id),
+    face INTEGER NOT NULL,
+    kind TEXT NOT NULL,
+    type TEXT NOT NULL COLLATE NOCASE
+);
+CREATE INDEX card_types_type ON card_types(type);
+CREATE INDEX card_types_card_id ON card_types(card_id);";
+
+// Every symbol in each face's mana cost, in the order they're printed. symbol is the text of the
+//  symbol like {W/U}
+const CREATE_MANA_SYMBOLS_TABLE_SQL: &str = "
+CREATE TABLE mana_symbols (
+    card_id INTEGER NOT NULL REFERENCES cards(id),
+    face INTEGER NOT NULL,
+    position INTEGER NOT NULL,
+    symbol TEXT NOT NULL,
+    hybrid INTEGER NOT NULL,
+    phyrexian INTEGER NOT NULL
+);
+CREATE INDEX mana_symbols_card_id ON mana_symbols(card_id);";
+
+// One row for each face with a power, toughness, loyalty or defense. Values that can't be
+//  compared (like ?) are NULL - see the stats module
+const CREATE_CARD_STATS_TABLE_SQL: &str = "
+CREATE TABLE card_stats (
+    card_id INTEGER NOT NULL REFERENCES cards(id),
+    face INTEGER NOT NULL,
+    power REAL,
+    toughness REAL,
+    loyalty REAL,
+    defense REAL
+);
+CREATE INDEX card_stats_card_id ON card_stats(card_id);";
+
+// Every printing of every card. The set, release date, and uri in cards are for the first print
+const CREATE_PRINTS_TABLE_SQL: &str = "
+CREATE TABLE prints (
+    scryfall_uuid BLOB PRIMARY KEY,
+    card_id INTEGER NOT NULL REFERENCES cards(id),
+    set_code TEXT NOT NULL,
     set_name TEXT NOT NULL,
-    released_at TEXT NOT NULL
+    collector_number TEXT NOT NULL,
+    rarity TEXT NOT NULL,
+    artist TEXT,
+    released_at TEXT NOT NULL,
+    scryfall_uri TEXT NOT NULL,
+    usd REAL,
+    usd_foil REAL,
+    usd_etched REAL,
+    eur REAL,
+    eur_foil REAL,
+    tix REAL,
+    flavour_text TEXT,
+    flavour_name TEXT,
+    watermark TEXT,
+    digital INTEGER NOT NULL,
+    promo INTEGER NOT NULL,
+    full_art INTEGER NOT NULL,
+    textless INTEGER NOT NULL
+);
+CREATE INDEX prints_card_id ON prints(card_id);";
+
+// Only sets with cards we've imported end up in here. card_count is the number of prints we have
+//  from the set, so it won't include tokens and the like
+const CREATE_SETS_TABLE_SQL: &str = "
+CREATE TABLE sets (
+    code TEXT PRIMARY KEY COLLATE NOCASE,
+    name TEXT NOT NULL,
+    set_type TEXT NOT NULL,
+    released_at TEXT NOT NULL,
+    card_count INTEGER NOT NULL
 )";
-// Because of how Scryfall gives this to us, other_card_name can mean the other side of the
-//  card or the adventure part of the card
-//  God help me if there's a card with adventure and another side
 
+// Keywords like Flying or Landfall. Scryfall has the same keyword on lots of cards, so they get
+//  their own table
+const CREATE_KEYWORDS_TABLE_SQL: &str = "
+CREATE TABLE keywords (
+    id INTEGER PRIMARY KEY,
+    name TEXT NOT NULL UNIQUE COLLATE NOCASE
+);
+CREATE TABLE card_keywords (
+    card_id INTEGER NOT NULL REFERENCES cards(id),
+    keyword_id INTEGER NOT NULL REFERENCES keywords(id),
+    PRIMARY KEY (card_id, keyword_id)
+);
+CREATE INDEX card_keywords_keyword_id ON card_keywords(keyword_id);";
+
+// kind is printed (printed_name) or flavour (flavor_name)
+const CREATE_CARD_ALIASES_TABLE_SQL: &str = "
+CREATE TABLE card_aliases (
+    card_id INTEGER NOT NULL REFERENCES cards(id),
+    alias TEXT NOT NULL,
+    search_alias TEXT NOT NULL,
+    kind TEXT NOT NULL,
+    PRIMARY KEY (card_id, alias)
+);
+CREATE INDEX card_aliases_search_alias ON card_aliases(search_alias);";
+
+// legality is one of Scryfall's legal, not_legal, banned, or restricted
+const CREATE_LEGALITIES_TABLE_SQL: &str = "
+CREATE TABLE legalities (
+    card_id INTEGER NOT NULL REFERENCES cards(id),
+    format TEXT NOT NULL,
+    legality TEXT NOT NULL,
+    PRIMARY KEY (card_id, format)
+);
+CREATE INDEX legalities_format ON legalities(format, legality);";
+
+// word_deletions is an index for finding close spellings without checking every word - see
+//  word_deletions()
 const CREATE_MAGIC_WORDS_TABLE_SQL: &str = "
 CREATE TABLE mtg_words (
+    id INTEGER PRIMARY KEY,
     word TEXT NOT NULL UNIQUE
-)";
+);
+CREATE TABLE word_deletions (
+    deletion TEXT NOT NULL,
+    word_id INTEGER NOT NULL REFERENCES mtg_words(id),
+    PRIMARY KEY (deletion, word_id)
+) WITHOUT ROWID;";
+
+const CREATE_CARD_INITIALISMS_TABLE_SQL: &str = "
+CREATE TABLE card_initialisms (
+    initialism TEXT NOT NULL,
+    card_id INTEGER NOT NULL REFERENCES cards(id),
+    PRIMARY KEY (initialism, card_id)
+) WITHOUT ROWID;";
+
+// Every word in every card name, for completing names as they're typed - see
+//  card_name_completions
+const CREATE_CARD_NAME_WORDS_TABLE_SQL: &str = "
+CREATE TABLE card_name_words (
+    word TEXT NOT NULL,
+    card_id INTEGER NOT NULL REFERENCES cards(id),
+    position INTEGER NOT NULL,
+    PRIMARY KEY (word, card_id, position)
+) WITHOUT ROWID;";
+
+// Nicknames card names aren't checked, because the cards aren't there yet
+const CREATE_NICKNAMES_TABLE_SQL: &str = "
+CREATE TABLE nicknames (
+    nickname TEXT NOT NULL,
+    card_name TEXT NOT NULL,
+    user_added INTEGER NOT NULL DEFAULT 0,
+    removed INTEGER NOT NULL DEFAULT 0,
+    PRIMARY KEY (nickname, card_name)
+);";
 
-// Will delete your current db
+// Will delete your current db, except for any nicknames you've added or removed
 pub fn init_db() {
     create_local_data_folder();
     let sqlite_file = get_local_data_sqlite_file();
     println!("sqlite file location: {}", sqlite_file.display());
+    let kept = match Connection::open(&sqlite_file) {
+        Ok(conn) => kept_nicknames(&conn),
+        Err(_) => KeptNicknames::default(),
+    };
     let _res = fs::remove_file(&sqlite_file);
     // TODO actually check result for whether it was a permissions thing or something
     let connection = Connection::open(sqlite_file).unwrap();
+    create_tables(&connection);
+    restore_nicknames(&connection, &kept);
+}
+
+fn create_tables(connection: &Connection) {
+    connection
+        .pragma_update(None, "user_version", SCHEMA_VERSION)
+        .unwrap();
     connection.execute(CREATE_CARDS_TABLE_SQL, ()).unwrap();
+    connection.execute(CREATE_CARD_FACES_TABLE_SQL, ()).unwrap();
+    connection.execute(CREATE_ORACLE_FTS_TABLE_SQL, ()).unwrap();
+    connection
+        .execute_batch(CREATE_CARD_TYPES_TABLE_SQL)
+        .unwrap();
     connection
-        .execute(CREATE_MAGIC_WORDS_TABLE_SQL, ())
+        .execute_batch(CREATE_MANA_SYMBOLS_TABLE_SQL)
         .unwrap();
+    connection
+        .execute_batch(CREATE_CARD_STATS_TABLE_SQL)
+        .unwrap();
+    connection.execute_batch(CREATE_PRINTS_TABLE_SQL).unwrap();
+    connection.execute(CREATE_SETS_TABLE_SQL, ()).unwrap();
+    connection
+        .execute_batch(CREATE_CARD_ALIASES_TABLE_SQL)
+        .unwrap();
+    connection.execute_batch(CREATE_KEYWORDS_TABLE_SQL).unwrap();
+    connection
+        .execute_batch(CREATE_LEGALITIES_TABLE_SQL)
+        .unwrap();
+    connection
+        .execute_batch(CREATE_MAGIC_WORDS_TABLE_SQL)
+        .unwrap();
+    connection
+        .execute_batch(CREATE_CARD_INITIALISMS_TABLE_SQL)
+        .unwrap();
+    connection
+        .execute_batch(CREATE_CARD_NAME_WORDS_TABLE_SQL)
+        .unwrap();
+    connection
+        .execute_batch(CREATE_NICKNAMES_TABLE_SQL)
+        .unwrap();
+    let bundled_nicknames =
+        parse_nickname_tsv(BUNDLED_NICKNAMES).expect("The bundled nicknames should be valid");
+    insert_nicknames(connection, &bundled_nicknames, false);
 }
 
-fn get_double_card(card: &ScryfallCard) -> DbCard {
-    let card_faces = card.card_faces.as_ref().unwrap();
-    let first_face = card_faces.first().unwrap();
-    let second_face = card_faces.get(1).unwrap();
-
-    // TODO - deduplicate this function and the parent function
-    let first_power_toughness = first_face
-        .power
-        .as_ref()
-        .map(|p| format!("{}/{}", p, first_face.toughness.clone().unwrap()));
-    let first_oracle_text = match first_face.oracle_text.clone() {
-        Some(ot) => ot,
-        None => "<No Oracle Text>".to_string(),
-    };
-
-    let second_power_toughness = second_face
-        .power
-        .as_ref()
-        .map(|p| format!("{}/{}", p, second_face.toughness.clone().unwrap()));
-    let second_oracle_text = match second_face.oracle_text.clone() {
-        Some(ot) => ot,
-        None => "<No Oracle Text>".to_string(),
-    };
-
+fn get_db_card(card: &ScryfallCard) -> DbCard {
+    let faces = get_faces(card);
+    // Reversible cards only have oracle ids on their faces
+    let oracle_id = card.oracle_id.or_else(|| {
+        card.card_faces
+            .iter()
+            .flatten()
+            .find_map(|face| face.oracle_id)
+    });
+    // Printed names (like Spider-Punk being printed as Kraza) go in card_aliases - see
+    //  insert_aliases
     DbCard {
         scryfall_uuid: card.id.to_bytes_le(),
-        oracle_uuid: card.oracle_id.unwrap().to_bytes_le(),
-        name: first_face.name.clone(),
-        type_line: first_face.type_line.clone().unwrap().clone(),
-        oracle_text: first_oracle_text,
-        power_toughness: first_power_toughness,
-        loyalty: first_face.loyalty.clone(),
-        mana_cost: first_face.mana_cost.clone(),
+        oracle_uuid: oracle_id.unwrap_or_default().to_bytes_le(),
+        name: faces[0].name.clone(),
+        layout: card.layout.clone(),
+        faces,
         scryfall_uri: Some(card.scryfall_uri.clone()),
-        oc_name: Some(second_face.name.clone()),
-        oc_type_line: second_face.type_line.clone(),
-        oc_oracle_text: Some(second_oracle_text),
-        oc_power_toughness: second_power_toughness,
-        oc_loyalty: second_face.loyalty.clone(),
-        oc_mana_cost: second_face.mana_cost.clone(),
         set_name: card.set_name.clone(),
         released_at: card.released_at.to_string(),
+        edhrec_rank: card.edhrec_rank,
     }
 }
 
+// Scryfall only has card_faces for cards with more than one face, but an empty list turns up
+//  now and then too. Everything that goes through the faces should use this.
+fn scryfall_faces(card: &ScryfallCard) -> Option<&[ScryfallCardFaceObject]> {
+    card.card_faces.as_deref().filter(|faces| !faces.is_empty())
+}
+
+// Anything missing from a face (like the type line on some reversible cards) comes from the card
+//  as a whole
+fn get_faces(card: &ScryfallCard) -> Vec<Face> {
+    // Vanilla cards have an empty oracle text
+    let oracle_text = |ot: Option<&String>| ot.filter(|ot| !ot.is_empty()).cloned();
+    let power_toughness = |power: Option<&String>, toughness: Option<&String>| {
+        power.zip(toughness).map(|(p, t)| format!("{}/{}", p, t))
+    };
+    let Some(card_faces) = scryfall_faces(card) else {
+        return vec![Face {
+            name: card.name.clone(),
+            mana_cost: card.mana_cost.clone(),
+            type_line: card.type_line.clone(),
+            oracle_text: oracle_text(card.oracle_text.as_ref()),
+            power_toughness: power_toughness(card.power.as_ref(), card.toughness.as_ref()),
+            loyalty: card.loyalty.clone(),
+            defense: card.defense.clone(),
+        }];
+    };
+    let card_type_lines: Vec<&str> = split_faces(&card.type_line).collect();
+    card_faces
+        .iter()
+        .enumerate()
+        .map(|(i, face)| Face {
+            name: face.name.clone(),
+            // Flip cards and the like have an empty cost on the faces that can't be cast
+            mana_cost: face.mana_cost.clone().filter(|mc| !mc.is_empty()),
+            type_line: face.type_line.clone().unwrap_or_else(|| {
+                card_type_lines
+                    .get(i)
+                    .copied()
+                    .unwrap_or(&card.type_line)
+                    .to_string()
+            }),
+            oracle_text: oracle_text(face.oracle_text.as_ref()),
+            power_toughness: power_toughness(face.power.as_ref(), face.toughness.as_ref()),
+            loyalty: face.loyalty.clone(),
+            defense: face.defense.clone(),
+        })
+        .collect()
+}
+
 pub fn get_db_connection() -> Connection {
     let sqlite_file = get_local_data_sqlite_file();
     Connection::open(sqlite_file).unwrap()
 }
 
-fn insert_card(tx: &Transaction, card: &DbCard) {
-    if card.name.contains("Punk") {
-        println!("Hello Insert Card?");
-    }
+// Returns the id of the new card, or None if the card was already there
+fn insert_card(tx: &Transaction, card: &DbCard) -> Option<i64> {
     let res = tx.execute(
-        "INSERT INTO cards (scryfall_uuid, oracle_uuid, name, type_line, oracle_text, power_toughness, loyalty, mana_cost, scryfall_uri, oc_name, oc_type_line, oc_oracle_text, oc_power_toughness, oc_mana_cost, set_name, released_at) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16)
+        "INSERT INTO cards (scryfall_uuid, oracle_uuid, name, search_name, layout, scryfall_uri, set_name, released_at) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)
 
     ON CONFLICT(scryfall_uuid) DO NOTHING
     ON CONFLICT(name) DO NOTHING;",
-            params![card.scryfall_uuid, card.oracle_uuid, deunicode(&card.name), card.type_line, card.oracle_text, card.power_toughness, card.loyalty, card.mana_cost, card.scryfall_uri, card.oc_name, card.oc_type_line, card.oc_oracle_text, card.oc_power_toughness, card.oc_mana_cost, card.set_name, card.released_at],
+            params![card.scryfall_uuid, card.oracle_uuid, card.name, normalise_search_text(&card.name), card.layout, card.scryfall_uri, card.set_name, card.released_at],
         );
-    if let Err(e) = res {
-        dbg!(e);
-        panic!("Error adding the card: {:?}", &card);
-    }
     /*
     To implement getting the earliest set & release date, I think I need to add something along the following:
     ON CONFLICT(scryfall_uuid) DO UPDATE
       SET set_name = CASE WHEN <release_date>... something something hopefully I don't need another SELECT, but I might...
     */
+    match res {
+        Ok(0) => None,
+        Ok(_) => Some(tx.last_insert_rowid()),
+        Err(e) => {
+            dbg!(e);
+            panic!("Error adding the card: {:?}", &card);
+        }
+    }
 }
 
-fn insert_words(tx: &Transaction, card: &DbCard) {
-    for word in card.name.split_whitespace() {
-        let word = deunicode(&word.to_lowercase());
-        if word.contains("//") {
-            continue;
-        }
+fn insert_faces(tx: &Transaction, card_id: i64, card: &DbCard) {
+    for (position, face) in card.faces.iter().enumerate() {
         let res = tx.execute(
-            "INSERT INTO mtg_words (word) VALUES (?1)
-                     ON CONFLICT (word) DO NOTHING;",
-            [word.replace(",", "")],
+            "INSERT INTO card_faces (card_id, face, name, search_name, mana_cost, type_line, oracle_text, power_toughness, loyalty, defense) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10);",
+            params![
+                card_id,
+                position,
+                face.name,
+                normalise_search_text(&face.name),
+                face.mana_cost,
+                face.type_line,
+                face.oracle_text,
+                face.power_toughness,
+                face.loyalty,
+                face.defense
+            ],
         );
         if let Err(e) = res {
             dbg!(e);
-            panic!("Error adding the word: {:?}", card);
+            panic!("Error adding the face: {:?}", face);
         }
     }
 }
 
-pub fn update_db_with_file(file: PathBuf, conn: &mut Connection) {
-    let ac = fs::read_to_string(&file).unwrap();
-    let ac: Vec<serde_json::Value> = serde_json::from_str(&ac).unwrap();
-    let tx = conn.transaction().unwrap();
+fn get_card_id(tx: &Transaction, name: &str) -> Option<i64> {
+    tx.query_row("SELECT id FROM cards WHERE name = ?1;", [name], |row| {
+        row.get(0)
+    })
+    .ok()
+}
 
-    for card in ac {
-        let card: Result<ScryfallCard, serde_json::Error> = serde_json::from_value(card);
-        let card = match card {
-            Err(e) => {
-                println!("{:?}", e);
-                continue;
+fn insert_print(tx: &Transaction, card_id: i64, card: &ScryfallCard) {
+    // Double faced cards have these on each face instead
+    let faces = card.card_faces.as_deref().unwrap_or_default();
+    let artist = card
+        .artist
+        .clone()
+        .or_else(|| faces.iter().find_map(|f| f.artist.clone()));
+    let flavour_text = card.flavour_text.clone().or_else(|| {
+        let face_flavour_text: Vec<&str> = faces
+            .iter()
+            .filter_map(|f| f.flavour_text.as_deref())
+            .collect();
+        (!face_flavour_text.is_empty()).then(|| face_flavour_text.join("\n"))
+    });
+    let watermark = card
+        .watermark
+        .clone()
+        .or_else(|| faces.iter().find_map(|f| f.watermark.clone()));
+    let res = tx.execute(
+        "INSERT INTO prints (scryfall_uuid, card_id, set_code, set_name, collector_number, rarity, artist, released_at, scryfall_uri, usd, usd_foil, usd_etched, eur, eur_foil, tix, flavour_text, flavour_name, watermark, digital, promo, full_art, textless) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18, ?19, ?20, ?21, ?22)
+    ON CONFLICT(scryfall_uuid) DO NOTHING;",
+        params![
+            card.id.to_bytes_le(),
+            card_id,
+            card.set,
+            card.set_name,
+            card.collector_number,
+            card.rarity.as_str(),
+            artist,
+            card.released_at.to_string(),
+            card.scryfall_uri,
+            card.prices.usd,
+            card.prices.usd_foil,
+            card.prices.usd_etched,
+            card.prices.eur,
+            card.prices.eur_foil,
+            card.prices.tix,
+            flavour_text,
+            card.flavour_name,
+            watermark,
+            card.digital,
+            card.promo,
+            card.full_art,
+            card.textless
+        ],
+    );
+    match res {
+        Ok(0) => (),
+        Ok(_) => insert_set(tx, card),
+        Err(e) => {
+            dbg!(e);
+            panic!("Error adding the print: {:?}", card.name);
+        }
+    }
+}
+
+fn insert_set(tx: &Transaction, card: &ScryfallCard) {
+    let res = tx.execute(
+        "INSERT INTO sets (code, name, set_type, released_at, card_count) VALUES (?1, ?2, ?3, ?4, 1)
+    ON CONFLICT(code) DO UPDATE SET card_count = card_count + 1, released_at = MIN(released_at, excluded.released_at);",
+        params![
+            card.set,
+            card.set_name,
+            card.set_type.as_str(),
+            card.released_at.to_string()
+        ],
+    );
+    if let Err(e) = res {
+        dbg!(e);
+        panic!("Error adding the set: {:?}", card.set_name);
+    }
+}
+
+// The bulk file isn't in release order, so the first print has to be worked out as we go
+fn update_first_print(tx: &Transaction, card_id: i64, card: &ScryfallCard) {
+    let res = tx.execute(
+        "UPDATE cards SET scryfall_uuid = ?2, set_name = ?3, released_at = ?4, scryfall_uri = ?5 WHERE id = ?1 AND released_at > ?4;",
+        params![
+            card_id,
+            card.id.to_bytes_le(),
+            card.set_name,
+            card.released_at.to_string(),
+            card.scryfall_uri
+        ],
+    );
+    if let Err(e) = res {
+        dbg!(e);
+        panic!("Error updating the first print: {:?}", card.name);
+    }
+}
+
+fn insert_oracle_text(tx: &Transaction, card_id: i64, card: &DbCard) {
+    let oracle_text = card
+        .faces
+        .iter()
+        .filter_map(|face| face.oracle_text.as_deref())
+        .collect::<Vec<&str>>()
+        .join("\n");
+    if oracle_text.is_empty() {
+        return;
+    }
+    let res = tx.execute(
+        "INSERT INTO oracle_fts (rowid, oracle_text) VALUES (?1, ?2);",
+        params![card_id, oracle_text],
+    );
+    if let Err(e) = res {
+        dbg!(e);
+        panic!("Error adding the oracle text: {:?}", card);
+    }
+}
+
+fn insert_types(tx: &Transaction, card_id: i64, card: &DbCard) {
+    for (face, Face { type_line, .. }) in card.faces.iter().enumerate() {
+        for (kind, card_type) in parse_type_line(type_line).all() {
+            let res = tx.execute(
+                "INSERT INTO card_types (card_id, face, kind, type) VALUES (?1, ?2, ?3, ?4);",
+                params![card_id, face, kind.as_str(), card_type],
+            );
+            if let Err(e) = res {
+                dbg!(e);
+                panic!("Error adding the type: {:?}", card);
             }
-            Ok(c) => c,
-        };
-        if card.name.contains("Punk") {
-            println!("{}", card.name);
         }
-        if card.name.contains("Magitek Infantry") {
-            println!("{}", card.name);
+    }
+}
+
+fn insert_colours(tx: &Transaction, card_id: i64, card: &ScryfallCard) {
+    let to_bits = |colours: Option<&Vec<Colour>>| colours.map(|c| Colours::from_scryfall(c).bits());
+
+    // Double faced cards only have colours on each face, but split cards and the like have them
+    //  at the top. A white card with a colourless back is still white.
+    let colours = scryfall_faces(card)
+        .into_iter()
+        .flatten()
+        .map(|face| face.colours.as_ref())
+        .chain([card.colours.as_ref()])
+        .filter_map(to_bits)
+        .reduce(|all, colours| all | colours);
+
+    let res = tx.execute(
+        "UPDATE cards SET colours = ?2, colour_identity = ?3, produced_mana = ?4 WHERE id = ?1;",
+        params![
+            card_id,
+            colours,
+            to_bits(card.colour_identity.as_ref()),
+            to_bits(card.produced_mana.as_ref())
+        ],
+    );
+    if let Err(e) = res {
+        dbg!(e);
+        panic!("Error adding the colours: {:?}", card.name);
+    }
+
+    let face_colours: Vec<[Option<&Vec<Colour>>; 2]> = match scryfall_faces(card) {
+        Some(faces) => faces
+            .iter()
+            .map(|f| {
+                [
+                    f.colours.as_ref().or(card.colours.as_ref()),
+                    f.colour_indicator.as_ref(),
+                ]
+            })
+            .collect(),
+        None => vec![[card.colours.as_ref(), card.colour_indicator.as_ref()]],
+    };
+    for (face, [colours, colour_indicator]) in face_colours.into_iter().enumerate() {
+        let res = tx.execute(
+            "UPDATE card_faces SET colours = ?3, colour_indicator = ?4 WHERE card_id = ?1 AND face = ?2;",
+            params![card_id, face, to_bits(colours), to_bits(colour_indicator)],
+        );
+        if let Err(e) = res {
+            dbg!(e);
+            panic!("Error adding the face colours: {:?}", card.name);
         }
-        // This should hopefully filter out Planes cards (but not Planeswalkers!)
-        if card.type_line.contains("Plane ") {
+    }
+}
+
+fn insert_card_flags(tx: &Transaction, card_id: i64, card: &ScryfallCard) {
+    let res = tx.execute(
+        "UPDATE cards SET reserved = ?2, game_changer = ?3, edhrec_rank = ?4, penny_rank = ?5 WHERE id = ?1;",
+        params![
+            card_id,
+            card.reserved,
+            card.game_changer,
+            card.edhrec_rank,
+            card.penny_rank
+        ],
+    );
+    if let Err(e) = res {
+        dbg!(e);
+        panic!("Error adding the card flags: {:?}", card.name);
+    }
+}
+
+fn insert_mana(tx: &Transaction, card_id: i64, card: &ScryfallCard, db_card: &DbCard) {
+    let res = tx.execute(
+        "UPDATE cards SET mana_value = ?2 WHERE id = ?1;",
+        params![card_id, card.cmc.map(|mv| mv.as_f64())],
+    );
+    if let Err(e) = res {
+        dbg!(e);
+        panic!("Error adding the mana value: {:?}", card.name);
+    }
+
+    for (face, Face { mana_cost, .. }) in db_card.faces.iter().enumerate() {
+        let Some(mana_cost) = mana_cost else {
             continue;
+        };
+        for (position, symbol) in parse_mana_cost(mana_cost).into_iter().enumerate() {
+            let res = tx.execute(
+                "INSERT INTO mana_symbols (card_id, face, position, symbol, hybrid, phyrexian) VALUES (?1, ?2, ?3, ?4, ?5, ?6);",
+                params![
+                    card_id,
+                    face,
+                    position,
+                    symbol.to_string(),
+                    symbol.is_hybrid(),
+                    symbol.is_phyrexian()
+                ],
+            );
+            if let Err(e) = res {
+                dbg!(e);
+                panic!("Error adding the mana symbol: {:?}", card.name);
+            }
         }
+    }
+}
 
-        // This should hopefully filter out art cards and similar sorts of non-card cards
-        if card.set_type == SetType::Memorabilia {
+fn insert_stats(tx: &Transaction, card_id: i64, card: &ScryfallCard) {
+    let faces: Vec<[Option<&String>; 4]> = match scryfall_faces(card) {
+        Some(faces) => faces
+            .iter()
+            .map(|f| {
+                [
+                    f.power.as_ref(),
+                    f.toughness.as_ref(),
+                    f.loyalty.as_ref(),
+                    f.defense.as_ref(),
+                ]
+            })
+            .collect(),
+        None => vec![[
+            card.power.as_ref(),
+            card.toughness.as_ref(),
+            card.loyalty.as_ref(),
+            card.defense.as_ref(),
+        ]],
+    };
+    for (face, stats) in faces.into_iter().enumerate() {
+        if stats.iter().all(|s| s.is_none()) {
             continue;
         }
+        let [power, toughness, loyalty, defense] = stats.map(|s| s.and_then(|s| parse_stat(s)));
+        let res = tx.execute(
+            "INSERT INTO card_stats (card_id, face, power, toughness, loyalty, defense) VALUES (?1, ?2, ?3, ?4, ?5, ?6);",
+            params![card_id, face, power, toughness, loyalty, defense],
+        );
+        if let Err(e) = res {
+            dbg!(e);
+            panic!("Error adding the stats: {:?}", card.name);
+        }
+    }
+}
 
-        // I don't think one would need to search for a token either
-        if card.set_type == SetType::Token {
-            continue;
+fn insert_keywords(tx: &Transaction, card_id: i64, card: &ScryfallCard) {
+    for keyword in &card.keywords {
+        let res = tx
+            .execute(
+                "INSERT INTO keywords (name) VALUES (?1) ON CONFLICT (name) DO NOTHING;",
+                [keyword],
+            )
+            .and_then(|_| {
+                tx.execute(
+                    "INSERT INTO card_keywords (card_id, keyword_id) SELECT ?1, id FROM keywords WHERE name = ?2
+                ON CONFLICT DO NOTHING;",
+                    params![card_id, keyword],
+                )
+            });
+        if let Err(e) = res {
+            dbg!(e);
+            panic!("Error adding the keyword: {:?}", card.name);
         }
-        if card.type_line.contains("Token") {
-            continue;
+    }
+}
+
+fn insert_legalities(tx: &Transaction, card_id: i64, card: &ScryfallCard) {
+    for (format, legality) in card.legalities.all() {
+        let res = tx.execute(
+            "INSERT INTO legalities (card_id, format, legality) VALUES (?1, ?2, ?3);",
+            params![card_id, format, legality.as_str()],
+        );
+        if let Err(e) = res {
+            dbg!(e);
+            panic!("Error adding the legality: {:?}", card.name);
         }
+    }
+}
 
-        // I don't even know what these are...
-        if card.set_type == SetType::Minigame {
-            continue;
+// The initials of the card's name (see name_initialisms), so "jvp" finds Jace, Vryn's Prodigy.
+// Each face gets its own, so Fire // Ice has none but Delver of Secrets // Insectile Aberration
+//  has "dos", "ds" and "ia"
+fn insert_initialisms(tx: &Transaction, card_id: i64, name: &str) {
+    for face_name in split_card_name(name) {
+        for initialism in name_initialisms(face_name) {
+            let res = tx.execute(
+                "INSERT INTO card_initialisms (initialism, card_id) VALUES (?1, ?2)
+                    ON CONFLICT DO NOTHING;",
+                params![initialism, card_id],
+            );
+            if let Err(e) = res {
+                dbg!(e);
+                panic!("Error adding the initialisms: {:?}", name);
+            }
         }
+    }
+}
 
-        if card.name.contains("Punk") {
-            println!("Not Token, memorabilia, or other weird stuff");
+fn insert_name_words(tx: &Transaction, card_id: i64, name: &str) {
+    for (position, word) in normalise_search_text(name).split_whitespace().enumerate() {
+        let res = tx.execute(
+            "INSERT INTO card_name_words (word, card_id, position) VALUES (?1, ?2, ?3)
+                ON CONFLICT DO NOTHING;",
+            params![word, card_id, position],
+        );
+        if let Err(e) = res {
+            dbg!(e);
+            panic!("Error adding the name words: {:?}", name);
         }
+    }
+}
 
-        // This is a hack so that I can get the first print. However, because the Through the Omenpath
-        //  cards are also considered re-prints (unsure how I feel about that), I need to avoid those.
-        //  If/when a Throught the Omenpath cards are re-printed, I'll have to rethink it all.
-        if card.reprint && !card.set_name.contains("Omenpath") {
+// Other names the card has been printed with, like the Godzilla series or Universes Within
+//  Spider-Man cards. Also done for reprints, since that's where most of these come from.
+fn insert_aliases(tx: &Transaction, card_id: i64, card: &ScryfallCard) {
+    let mut aliases = Vec::new();
+    if let Some(printed_name) = &card.printed_name {
+        aliases.push((printed_name, "printed"));
+    }
+    if let Some(flavour_name) = &card.flavour_name {
+        aliases.push((flavour_name, "flavour"));
+    }
+    for face in card.card_faces.iter().flatten() {
+        if let Some(printed_name) = &face.printed_name {
+            aliases.push((printed_name, "printed"));
+        }
+    }
+    let face_names: Vec<&str> = split_card_name(&card.name).collect();
+    for (alias, kind) in aliases {
+        if *alias == card.name || face_names.contains(&alias.as_str()) {
             continue;
         }
-
-        if card.name.contains("Punk") {
-            println!("Hello?");
+        let res = tx.execute(
+            "INSERT INTO card_aliases (card_id, alias, search_alias, kind) VALUES (?1, ?2, ?3, ?4)
+                ON CONFLICT DO NOTHING;",
+            params![card_id, alias, normalise_search_text(alias), kind],
+        );
+        if let Err(e) = res {
+            dbg!(e);
+            panic!("Error adding the alias: {:?}", card.name);
         }
+        insert_words(tx, alias);
+    }
+}
 
-        if card.card_faces.is_some() {
-            if card.name.contains("Punk") {
-                println!("Hello1?");
+// Multi-faced cards have names like "Fire // Ice"
+fn split_card_name(name: &str) -> impl Iterator<Item = &str> {
+    name.split(" // ")
+}
+
+fn insert_words(tx: &Transaction, name: &str) {
+    for word in normalise_search_text(name).split_whitespace() {
+        let res = tx.execute(
+            "INSERT INTO mtg_words (word) VALUES (?1)
+                     ON CONFLICT (word) DO NOTHING;",
+            params![word],
+        );
+        match res {
+            Ok(0) => (),
+            Ok(_) => {
+                let word_id = tx.last_insert_rowid();
+                // Lots of these, so the statement is only prepared the once
+                let mut stmt = tx
+                    .prepare_cached(
+                        "INSERT INTO word_deletions (deletion, word_id) VALUES (?1, ?2);",
+                    )
+                    .unwrap();
+                for deletion in word_deletions(word, MAX_SPELLING_DISTANCE) {
+                    stmt.execute(params![deletion, word_id]).unwrap();
+                }
+            }
+            Err(e) => {
+                dbg!(e);
+                panic!("Error adding the word: {:?}", name);
             }
-            let card = get_double_card(&card);
-            insert_card(&tx, &card);
-            insert_words(&tx, &card);
-            continue;
         }
+    }
+}
 
-        if card.name.contains("Punk") {
-            println!("Hello2?");
-        }
-
-        // This will likely need to be reviewed if/when a 2 faced card is printed similar
-        //  to Spider-Man/Universes Within
-        // FIXME - Scryfall I think has changed what "printed name" means - Spider-Punk is printed as Kraza
-        //  Problem is I can't remember why I did this in the first place...
-        //let name = match card.printed_name {
-        //    Some(pn) => pn,
-        //    None => card.name.clone(),
-        //};
-
-        //if card.name.contains("Punk") {
-        //    println!("{} -> {}", card.name, name);
-        //}
-
-        let card = DbCard {
-            scryfall_uuid: card.id.to_bytes_le(),
-            oracle_uuid: card.oracle_id.unwrap().to_bytes_le(),
-            name: card.name,
-            type_line: card.type_line,
-            oracle_text: match card.oracle_text {
-                Some(ref ot) => ot.to_string(),
-                None => "<No Oracle Text>".to_string(),
-            },
-            power_toughness: match card.power {
-                Some(ref p) => Some(format!("{}/{}", p, card.toughness.clone().unwrap())),
-                None => None,
-            },
-            loyalty: card.loyalty,
-            mana_cost: card.mana_cost,
-            scryfall_uri: Some(card.scryfall_uri),
-            set_name: card.set_name,
-            released_at: card.released_at.to_string(),
-            ..Default::default()
+pub fn update_db_with_file(file: PathBuf, conn: &mut Connection) {
+    let ac = fs::read_to_string(&file).unwrap();
+    let ac: Vec<serde_json::Value> = serde_json::from_str(&ac).unwrap();
+    let tx = conn.transaction().unwrap();
+
+    for card in ac {
+        let card: Result<ScryfallCard, serde_json::Error> = serde_json::from_value(card);
+        let card = match card {
+            Err(e) => {
+                println!("{:?}", e);
+                continue;
+            }
+            Ok(c) => c,
         };
-        if card.name.contains("Punk") {
-            println!("Hello3?");
-        }
-        insert_card(&tx, &card);
-        insert_words(&tx, &card);
+        import_card(&tx, card);
     }
 
     let res = tx.commit();
@@ -564,18 +1801,805 @@ pub fn update_db_with_file(file: PathBuf, conn: &mut Connection) {
     }
 }
 
+fn import_card(tx: &Transaction, card: ScryfallCard) {
+    if card.name.contains("Magitek Infantry") {
+        println!("{}", card.name);
+    }
+    let face_types: Vec<TypeLine> = split_faces(&card.type_line).map(parse_type_line).collect();
+
+    // Filter out Planes cards (but not Planeswalkers!)
+    if face_types.iter().any(|t| t.has_card_type("Plane")) {
+        return;
+    }
+
+    // This should hopefully filter out art cards and similar sorts of non-card cards
+    if card.set_type == SetType::Memorabilia {
+        return;
+    }
+
+    // I don't think one would need to search for a token either
+    if card.set_type == SetType::Token {
+        return;
+    }
+    if face_types.iter().any(|t| t.has_supertype("Token")) {
+        return;
+    }
+
+    // I don't even know what these are...
+    if card.set_type == SetType::Minigame {
+        return;
+    }
+
+    let db_card = get_db_card(&card);
+    if let Some(card_id) = get_card_id(tx, &db_card.name) {
+        // Already have the card from another print
+        update_first_print(tx, card_id, &card);
+        insert_print(tx, card_id, &card);
+        insert_aliases(tx, card_id, &card);
+        return;
+    }
+    insert_words(tx, &card.name);
+    let Some(card_id) = insert_card(tx, &db_card) else {
+        return;
+    };
+    insert_faces(tx, card_id, &db_card);
+    insert_oracle_text(tx, card_id, &db_card);
+    insert_types(tx, card_id, &db_card);
+    insert_colours(tx, card_id, &card);
+    insert_card_flags(tx, card_id, &card);
+    insert_mana(tx, card_id, &card, &db_card);
+    insert_stats(tx, card_id, &card);
+    insert_legalities(tx, card_id, &card);
+    insert_keywords(tx, card_id, &card);
+    insert_print(tx, card_id, &card);
+    insert_aliases(tx, card_id, &card);
+    insert_initialisms(tx, card_id, &card.name);
+    insert_name_words(tx, card_id, &card.name);
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::query::parse_query;
     use std::path::PathBuf;
 
     fn init_test_db_and_get_db_connection() -> Connection {
         let connection = Connection::open_in_memory().unwrap();
-        connection.execute(CREATE_CARDS_TABLE_SQL, ()).unwrap();
+        create_tables(&connection);
         connection
-            .execute(CREATE_MAGIC_WORDS_TABLE_SQL, ())
+    }
+
+    // Loads the handful of single card json files in test_files/ so tests don't need the full
+    //  Scryfall bulk download
+    fn init_test_db_with_test_cards() -> Connection {
+        let mut conn = init_test_db_and_get_db_connection();
+        for file_name in ["nissa.json", "black_lotus.json", "little_girl.json"] {
+            import_test_card_with(&mut conn, file_name, |_| ());
+        }
+        conn
+    }
+
+    // Imports one of the test_files cards after edit has changed it. Made up cards need their own
+    //  id, and new cards their own name and scryfall_uri too.
+    fn import_test_card_with(
+        conn: &mut Connection,
+        file_name: &str,
+        edit: impl FnOnce(&mut serde_json::Value),
+    ) {
+        let mut f = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        f.push("test_files");
+        f.push(file_name);
+        let mut card: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(f).unwrap()).unwrap();
+        edit(&mut card);
+        let tx = conn.transaction().unwrap();
+        import_card(&tx, serde_json::from_value(card).unwrap());
+        tx.commit().unwrap();
+    }
+
+    fn names(cards: Vec<DbCard>) -> Vec<String> {
+        cards.into_iter().map(|c| c.name).collect()
+    }
+
+    fn names_matching(conn: &Connection, query: &str) -> Vec<String> {
+        let mut names = names(query_cards(conn, &parse_query(query).unwrap()).unwrap());
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn test_query_cards() {
+        let conn = init_test_db_with_test_cards();
+        assert_eq!(
+            names_matching(&conn, "nissa"),
+            vec!["Nissa, Resurgent Animist"]
+        );
+        assert_eq!(
+            names_matching(&conn, "t:creature -t:elf"),
+            vec!["Little Girl"]
+        );
+        assert_eq!(
+            names_matching(&conn, "girl or (t:legendary o:landfall)"),
+            vec!["Little Girl", "Nissa, Resurgent Animist"]
+        );
+        assert_eq!(
+            names_matching(&conn, "!\"little girl\""),
+            vec!["Little Girl"]
+        );
+        assert!(names_matching(&conn, "!little").is_empty());
+        assert!(query_has_cards(&conn, &parse_query("girl or lotus").unwrap()).unwrap());
+        assert!(!query_has_cards(&conn, &parse_query("girl lotus").unwrap()).unwrap());
+    }
+
+    #[test]
+    fn test_colour_queries() {
+        let mut conn = init_test_db_with_test_cards();
+        assert_eq!(
+            names_matching(&conn, "c:g"),
+            vec!["Nissa, Resurgent Animist"]
+        );
+        assert_eq!(names_matching(&conn, "c:w"), vec!["Little Girl"]);
+        assert!(names_matching(&conn, "c:wg").is_empty());
+        assert_eq!(
+            names_matching(&conn, "id<=selesnya"),
+            vec!["Black Lotus", "Little Girl", "Nissa, Resurgent Animist"]
+        );
+        assert_eq!(
+            names_matching(&conn, "id:esper"),
+            vec!["Black Lotus", "Little Girl"]
+        );
+        assert_eq!(
+            names_matching(&conn, "c=1 -c:m"),
+            names_matching(&conn, "c<=wubrg -c:c")
+        );
+        assert_eq!(
+            names_matching(&conn, "c!=c"),
+            vec!["Little Girl", "Nissa, Resurgent Animist"]
+        );
+        assert_eq!(names_matching(&conn, "c:c"), vec!["Black Lotus"]);
+        assert_eq!(
+            names_matching(&conn, "c<m"),
+            vec!["Black Lotus", "Little Girl", "Nissa, Resurgent Animist"]
+        );
+        assert!(names_matching(&conn, "c>=m").is_empty());
+        assert_eq!(
+            names_matching(&conn, "produces:wubrg"),
+            vec!["Black Lotus", "Nissa, Resurgent Animist"]
+        );
+        assert!(names_matching(&conn, "produces:c").is_empty());
+
+        // A made up transform card with a white front and a colourless back
+        import_test_card_with(&mut conn, "little_girl.json", |card| {
+            card["id"] = "e2b7c4d9-1a6f-4c3e-8b5d-9f0a2c7e4b13".into();
+            card["name"] = "Lost Girl // Lost Ghost".into();
+            card["layout"] = "transform".into();
+            card["scryfall_uri"] = "https://scryfall.com/card/all/997/lost-girl".into();
+            card.as_object_mut().unwrap().remove("colors");
+            card["card_faces"] = serde_json::json!([
+                {"object": "card_face", "name": "Lost Girl", "mana_cost": "{HW}", "type_line": "Creature — Human Child", "colors": ["W"]},
+                {"object": "card_face", "name": "Lost Ghost", "mana_cost": "", "type_line": "Creature — Spirit", "colors": []}
+            ]);
+        });
+        for search in ["c:w", "c=w", "c=1", "-c:c"] {
+            assert!(
+                names_matching(&conn, search).contains(&"Lost Girl".to_string()),
+                "{}",
+                search
+            );
+        }
+        for search in ["c:c", "c=0", "c!=w", "c:m"] {
+            assert!(
+                !names_matching(&conn, search).contains(&"Lost Girl".to_string()),
+                "{}",
+                search
+            );
+        }
+    }
+
+    #[test]
+    fn test_mana_queries() {
+        let conn = init_test_db_with_test_cards();
+        assert_eq!(names_matching(&conn, "mv=0.5"), vec!["Little Girl"]);
+        assert_eq!(
+            names_matching(&conn, "mv<=3"),
+            vec!["Black Lotus", "Little Girl", "Nissa, Resurgent Animist"]
+        );
+        assert_eq!(
+            names_matching(&conn, "mv=odd"),
+            vec!["Nissa, Resurgent Animist"]
+        );
+        assert_eq!(names_matching(&conn, "mv=even"), vec!["Black Lotus"]);
+        assert_eq!(
+            names_matching(&conn, "m:{G}"),
+            vec!["Nissa, Resurgent Animist"]
+        );
+        assert_eq!(
+            names_matching(&conn, "m:2g"),
+            vec!["Nissa, Resurgent Animist"]
+        );
+        assert!(names_matching(&conn, "m:{G}{G}").is_empty());
+        assert_eq!(
+            names_matching(&conn, "m={G}{2}"),
+            vec!["Nissa, Resurgent Animist"]
+        );
+        assert!(names_matching(&conn, "m={G}").is_empty());
+        assert_eq!(names_matching(&conn, "m:{HW}"), vec!["Little Girl"]);
+        assert!(names_matching(&conn, "is:hybrid or is:phyrexian or m:x").is_empty());
+    }
+
+    #[test]
+    fn test_stat_queries() {
+        let conn = init_test_db_with_test_cards();
+        assert_eq!(
+            names_matching(&conn, "pow>=3"),
+            vec!["Nissa, Resurgent Animist"]
+        );
+        assert_eq!(names_matching(&conn, "tou<1"), vec!["Little Girl"]);
+        assert_eq!(
+            names_matching(&conn, "pow:3 tou=pow"),
+            vec!["Nissa, Resurgent Animist"]
+        );
+        assert!(names_matching(&conn, "tou>pow").is_empty());
+        assert!(names_matching(&conn, "loy>=0 or def>0").is_empty());
+    }
+
+    #[test]
+    fn test_legality_queries() {
+        let conn = init_test_db_with_test_cards();
+        assert_eq!(
+            names_matching(&conn, "f:modern"),
+            vec!["Nissa, Resurgent Animist"]
+        );
+        assert_eq!(
+            names_matching(&conn, "f:vintage"),
+            vec!["Black Lotus", "Nissa, Resurgent Animist"]
+        );
+        assert_eq!(names_matching(&conn, "banned:legacy"), vec!["Black Lotus"]);
+        assert_eq!(
+            names_matching(&conn, "restricted:vintage"),
+            vec!["Black Lotus"]
+        );
+        assert!(names_matching(&conn, "banned:modern").is_empty());
+        assert!(query_cards(&conn, &parse_query("f:chess").unwrap()).is_err());
+
+        let legalities = card_legalities(&conn, "Black Lotus");
+        assert_eq!(legalities.len(), 21);
+        assert!(legalities.contains(&("vintage".to_string(), Legality::Restricted)));
+    }
+
+    #[test]
+    fn test_prints() {
+        let mut conn = init_test_db_with_test_cards();
+        // Pretend the Alpha printing turns up in the bulk file after the VMA one
+        import_test_card_with(&mut conn, "black_lotus.json", |alpha| {
+            alpha["id"] = "b0faa7f2-b547-42c4-a810-839da50dadfe".into();
+            alpha["set"] = "lea".into();
+            alpha["set_name"] = "Limited Edition Alpha".into();
+            alpha["collector_number"] = "232".into();
+            alpha["rarity"] = "rare".into();
+            alpha["released_at"] = "1993-08-05".into();
+            alpha["reprint"] = false.into();
+            alpha["scryfall_uri"] = "https://scryfall.com/card/lea/232/black-lotus".into();
+        });
+
+        let prints = card_prints(&conn, "Black Lotus");
+        let sets: Vec<&str> = prints.iter().map(|p| p.set_code.as_str()).collect();
+        assert_eq!(sets, vec!["lea", "vma"]);
+        assert_eq!(prints[0].rarity, "rare");
+        let first_set: String = conn
+            .query_row(
+                "SELECT set_name FROM cards WHERE name = 'Black Lotus';",
+                [],
+                |row| row.get(0),
+            )
             .unwrap();
-        connection
+        assert_eq!(first_set, "Limited Edition Alpha");
+        assert_eq!(names_matching(&conn, "black lotus"), vec!["Black Lotus"]);
+    }
+
+    #[test]
+    fn test_set_queries() {
+        let conn = init_test_db_with_test_cards();
+        assert_eq!(
+            names_matching(&conn, "s:mat"),
+            vec!["Nissa, Resurgent Animist"]
+        );
+        assert_eq!(names_matching(&conn, "e:VMA"), vec!["Black Lotus"]);
+        assert_eq!(names_matching(&conn, "st:funny"), vec!["Little Girl"]);
+        assert_eq!(
+            names_matching(&conn, "year>=2014"),
+            vec!["Black Lotus", "Nissa, Resurgent Animist"]
+        );
+        assert_eq!(
+            names_matching(&conn, "date<2014-01-01"),
+            vec!["Little Girl"]
+        );
+        assert!(query_cards(&conn, &parse_query("date<yesterday").unwrap()).is_err());
+
+        let sets = all_sets(&conn);
+        let codes: Vec<&str> = sets.iter().map(|s| s.code.as_str()).collect();
+        assert_eq!(codes, vec!["unh", "vma", "mat"]);
+        assert_eq!(sets[0].set_type, "funny");
+        assert_eq!(sets[0].card_count, 1);
+    }
+
+    #[test]
+    fn test_print_queries() {
+        let conn = init_test_db_with_test_cards();
+        assert_eq!(
+            names_matching(&conn, "r:m"),
+            vec!["Nissa, Resurgent Animist"]
+        );
+        assert_eq!(
+            names_matching(&conn, "r>=rare"),
+            vec!["Black Lotus", "Nissa, Resurgent Animist"]
+        );
+        assert_eq!(names_matching(&conn, "r<uncommon"), vec!["Little Girl"]);
+        assert_eq!(names_matching(&conn, "s:vma cn:4"), vec!["Black Lotus"]);
+        // Both have to match the same printing
+        assert!(names_matching(&conn, "s:vma r:mythic").is_empty());
+        assert_eq!(
+            names_matching(&conn, "s:vma or r:mythic"),
+            vec!["Black Lotus", "Nissa, Resurgent Animist"]
+        );
+
+        let (card, print) = print_by_set_and_number(&conn, "VMA", "4").unwrap();
+        assert_eq!(card.name, "Black Lotus");
+        assert_eq!(print.rarity, "bonus");
+        assert!(print_by_set_and_number(&conn, "vma", "5").is_none());
+    }
+
+    #[test]
+    fn test_price_queries() {
+        let conn = init_test_db_with_test_cards();
+        assert_eq!(names_matching(&conn, "usd<5"), vec!["Little Girl"]);
+        assert_eq!(
+            names_matching(&conn, "eur>=20"),
+            vec!["Nissa, Resurgent Animist"]
+        );
+        assert_eq!(
+            names_matching(&conn, "tix<0.2"),
+            vec!["Nissa, Resurgent Animist"]
+        );
+        // Foil prices count too
+        assert_eq!(names_matching(&conn, "usd>15 usd<16"), vec!["Little Girl"]);
+
+        let (print, price) =
+            cheapest_print(card_prints(&conn, "Little Girl"), Currency::Usd).unwrap();
+        assert_eq!(print.set_code, "unh");
+        assert_eq!(price, 0.31);
+        assert!(cheapest_print(card_prints(&conn, "Black Lotus"), Currency::Eur).is_none());
+    }
+
+    #[test]
+    fn test_keyword_queries() {
+        let conn = init_test_db_with_test_cards();
+        assert_eq!(
+            names_matching(&conn, "kw:landfall"),
+            vec!["Nissa, Resurgent Animist"]
+        );
+        assert_eq!(
+            names_matching(&conn, "-keyword:Landfall"),
+            vec!["Black Lotus", "Little Girl"]
+        );
+        assert!(names_matching(&conn, "kw:flying").is_empty());
+        assert_eq!(
+            card_keywords(&conn, "Nissa, Resurgent Animist"),
+            vec!["Landfall"]
+        );
+        assert_eq!(all_keywords(&conn), vec![("Landfall".to_string(), 1)]);
+    }
+
+    #[test]
+    fn test_artist_and_flavour_queries() {
+        let conn = init_test_db_with_test_cards();
+        assert_eq!(
+            names_matching(&conn, "a:\"rebecca guay\""),
+            vec!["Little Girl"]
+        );
+        assert_eq!(
+            names_matching(&conn, "ft:\"pee the bed\""),
+            vec!["Little Girl"]
+        );
+        assert_eq!(
+            names_matching(&conn, "wm:desparked"),
+            vec!["Nissa, Resurgent Animist"]
+        );
+        assert!(names_matching(&conn, "wm:orzhov").is_empty());
+
+        let prints = artist_prints(&conn, "chris");
+        assert_eq!(prints.len(), 1);
+        assert_eq!(prints[0].0, "Black Lotus");
+        assert_eq!(prints[0].1.artist.as_deref(), Some("Chris Rahn"));
+        assert!(artist_prints(&conn, "%").is_empty());
+        assert!(artist_prints(&conn, "chr_s").is_empty());
+    }
+
+    #[test]
+    fn test_close_words() {
+        let conn = init_test_db_with_test_cards();
+        // Transposed letters are one edit
+        assert_eq!(
+            close_mtg_words(&conn, "blakc", 2),
+            vec![(1, "black".to_string())]
+        );
+        assert_eq!(
+            close_mtg_words(&conn, "Lotus", 2),
+            vec![(0, "lotus".to_string())]
+        );
+        assert_eq!(
+            close_mtg_words(&conn, "lotsu", 2),
+            vec![(1, "lotus".to_string())]
+        );
+        // Only words sharing a deletion come out of the index, not every word of about that length
+        let candidates = spelling_candidates(&conn, "blakc", 2);
+        assert!(candidates.contains(&"black".to_string()));
+        assert!(!candidates.contains(&"lotus".to_string()));
+        assert_eq!(
+            close_mtg_words(&conn, "resurgnet", 2),
+            vec![(1, "resurgent".to_string())]
+        );
+        assert_eq!(
+            close_mtg_words(&conn, "rezurgnet", 2),
+            vec![(2, "resurgent".to_string())]
+        );
+        assert!(close_mtg_words(&conn, "rezurgnet", 1).is_empty());
+        assert!(close_mtg_words(&conn, "blakc", 0).is_empty());
+        // The index only goes so far
+        assert_eq!(
+            close_mtg_words(&conn, "rezurgnet", 3),
+            close_mtg_words(&conn, "rezurgnet", 2)
+        );
+
+        let mut deletions: Vec<String> = word_deletions("abc", 1).into_iter().collect();
+        deletions.sort();
+        assert_eq!(deletions, vec!["ab", "abc", "ac", "bc"]);
+    }
+
+    #[test]
+    fn test_card_names_with_words() {
+        let conn = init_test_db_with_test_cards();
+        let words = vec!["lotus".to_string(), "GIRL".to_string()];
+        assert_eq!(
+            card_names_with_words(&conn, &words),
+            vec![
+                ("Black Lotus".to_string(), "Black Lotus".to_string()),
+                ("Little Girl".to_string(), "Little Girl".to_string())
+            ]
+        );
+        assert!(card_names_with_words(&conn, &[]).is_empty());
+    }
+
+    #[test]
+    fn test_name_completions() {
+        let conn = init_test_db_with_test_cards();
+        assert_eq!(card_name_completions(&conn, "lo", 10), vec!["Black Lotus"]);
+        // Names starting with the word go first
+        assert_eq!(
+            card_name_completions(&conn, "L", 10),
+            vec!["Little Girl", "Black Lotus"]
+        );
+        assert_eq!(card_name_completions(&conn, "l", 1), vec!["Little Girl"]);
+        assert_eq!(
+            card_name_completions(&conn, "res ANIM", 10),
+            vec!["Nissa, Resurgent Animist"]
+        );
+        assert_eq!(
+            card_name_completions(&conn, "nissa,", 10),
+            vec!["Nissa, Resurgent Animist"]
+        );
+        assert!(card_name_completions(&conn, "lotus girl", 10).is_empty());
+        assert!(card_name_completions(&conn, " ", 10).is_empty());
+    }
+
+    #[test]
+    fn test_initialisms() {
+        let conn = init_test_db_with_test_cards();
+        let initialism_names = |initialism: &str| names(cards_for_initialism(&conn, initialism));
+        assert_eq!(initialism_names("nra"), vec!["Nissa, Resurgent Animist"]);
+        assert_eq!(initialism_names("BL"), vec!["Black Lotus"]);
+        assert_eq!(initialism_names("lg"), vec!["Little Girl"]);
+        assert_eq!(initialism_names("n.r.a."), vec!["Nissa, Resurgent Animist"]);
+        assert_eq!(initialism_names("b l"), vec!["Black Lotus"]);
+        assert!(initialism_names("bg").is_empty());
+    }
+
+    #[test]
+    fn test_schema_version() {
+        let conn = init_test_db_and_get_db_connection();
+        assert!(schema_is_current(&conn));
+        conn.pragma_update(None, "user_version", 0).unwrap();
+        assert!(!schema_is_current(&conn));
+    }
+
+    #[test]
+    fn test_nicknames() {
+        let conn = init_test_db_with_test_cards();
+        let nickname_names = |nickname: &str| names(cards_for_nickname(&conn, nickname));
+        // Bundled, but only Black Lotus is in the test db
+        assert_eq!(nickname_names(" POWER "), vec!["Black Lotus"]);
+        assert!(nickname_names("bob").is_empty());
+
+        assert_eq!(
+            insert_user_nickname(&conn, "girl", "little girl"),
+            Ok("Little Girl".to_string())
+        );
+        assert_eq!(
+            insert_user_nickname(&conn, "girl", "black lotus"),
+            Ok("Black Lotus".to_string())
+        );
+        assert_eq!(
+            insert_user_nickname(&conn, "girl", "Big Girl"),
+            Err(NicknameError::CardNotFound("Big Girl".to_string()))
+        );
+        assert_eq!(nickname_names("Girl"), vec!["Black Lotus", "Little Girl"]);
+        assert_eq!(kept_nicknames(&conn).added.len(), 2);
+
+        assert_eq!(delete_nickname(&conn, "girl", Some("black lotus")), 1);
+        assert_eq!(nickname_names("girl"), vec!["Little Girl"]);
+        assert_eq!(delete_nickname(&conn, "power", None), 9);
+        assert!(nickname_names("power").is_empty());
+        assert!(all_nicknames(&conn).contains(&("girl".to_string(), "Little Girl".to_string())));
+        assert_eq!(delete_nickname(&conn, "power", None), 0);
+
+        // Like init_db - the removed bundled nicknames stay removed in the new db
+        let kept = kept_nicknames(&conn);
+        let new_conn = init_test_db_and_get_db_connection();
+        restore_nicknames(&new_conn, &kept);
+        assert_eq!(
+            all_nicknames(&new_conn)
+                .iter()
+                .filter(|(nickname, _)| nickname == "power")
+                .count(),
+            0
+        );
+        assert!(
+            all_nicknames(&new_conn).contains(&("girl".to_string(), "Little Girl".to_string()))
+        );
+
+        // Adding one back undoes the removal
+        insert_user_nickname(&conn, "power", "black lotus").unwrap();
+        assert_eq!(nickname_names("power"), vec!["Black Lotus"]);
+    }
+
+    #[test]
+    fn test_search_normalisation() {
+        let mut conn = init_test_db_with_test_cards();
+        import_test_card_with(&mut conn, "little_girl.json", |card| {
+            card["id"] = "0f4c2a53-1d3e-4f6b-9a7c-2b8e5d1c9f00".into();
+            card["name"] = "Lim-Dûl's Vault".into();
+            card["scryfall_uri"] = "https://scryfall.com/card/all/281/lim-duls-vault".into();
+        });
+
+        // Shown as printed, found however it's typed
+        for search in ["lim-dûl's", "lim-dul's", "LIM DULS", "!\"lim duls vault\""] {
+            assert_eq!(names_matching(&conn, search), vec!["Lim-Dûl's Vault"]);
+        }
+        assert_eq!(
+            card_by_name(&conn, "Lim-Dul's Vault").unwrap().name,
+            "Lim-Dûl's Vault"
+        );
+        assert_eq!(
+            names_matching(&conn, "\"nissa resurgent\""),
+            vec!["Nissa, Resurgent Animist"]
+        );
+        assert_eq!(
+            close_mtg_words(&conn, "Dûl's", 0),
+            vec![(0, "duls".to_string())]
+        );
+        assert_eq!(
+            card_name_completions(&conn, "dul", 10),
+            vec!["Lim-Dûl's Vault"]
+        );
+    }
+
+    #[test]
+    fn test_card_faces() {
+        let mut conn = init_test_db_with_test_cards();
+        // A made up split card with three halves, the last of which is missing a few things
+        import_test_card_with(&mut conn, "little_girl.json", |card| {
+            card["id"] = "7a0e9c1b-3f52-4d8e-b6a1-5c2d9e4f8b10".into();
+            card["name"] = "Who // What // When".into();
+            card["layout"] = "split".into();
+            card["type_line"] = "Instant // Instant // Sorcery".into();
+            card["scryfall_uri"] = "https://scryfall.com/card/all/999/who-what-when".into();
+            card["card_faces"] = serde_json::json!([
+                {"object": "card_face", "name": "Who", "mana_cost": "{X}{W}", "type_line": "Instant", "oracle_text": "Target player gains X life."},
+                {"object": "card_face", "name": "What", "mana_cost": "{2}{R}", "type_line": "Instant", "oracle_text": "Destroy target artifact."},
+                {"object": "card_face", "name": "When", "mana_cost": "{2}{U}", "oracle_text": "Counter target creature spell.", "power": "1", "colors": ["U"]}
+            ]);
+        });
+
+        let card = card_by_name(&conn, "Who").unwrap();
+        assert_eq!(card.layout, "split");
+        assert_eq!(card.full_name(), "Who // What // When");
+        let face_names: Vec<&str> = card.faces.iter().map(|f| f.name.as_str()).collect();
+        assert_eq!(face_names, vec!["Who", "What", "When"]);
+        assert_eq!(card.faces[2].type_line, "Sorcery");
+        assert_eq!(card.faces[2].power_toughness, None);
+        let display = card.to_string();
+        for text in [
+            "Who\t{X}{W}",
+            "What\t{2}{R}",
+            "When\t{2}{U}",
+            "Counter target",
+        ] {
+            assert!(display.contains(text), "{} isn't in {}", text, display);
+        }
+
+        // Every face can be searched, not just the first two
+        for search in ["when", "o:\"counter target\"", "t:sorcery", "m:{U}", "c:u"] {
+            assert_eq!(names_matching(&conn, search), vec!["Who"], "{}", search);
+        }
+
+        // An empty card_faces is the same as not having any
+        import_test_card_with(&mut conn, "little_girl.json", |card| {
+            card["id"] = "c3f1d7a2-8e4b-4b1f-9d2a-6e5c7b8a9f01".into();
+            card["name"] = "Faceless Girl".into();
+            card["scryfall_uri"] = "https://scryfall.com/card/all/998/faceless-girl".into();
+            card["card_faces"] = serde_json::json!([]);
+        });
+        let faceless = card_by_name(&conn, "Faceless Girl").unwrap();
+        assert_eq!(faceless.faces.len(), 1);
+        assert_eq!(
+            names_matching(&conn, "tou<1 girl"),
+            vec!["Faceless Girl", "Little Girl"]
+        );
+
+        let lotus = card_by_name(&conn, "Black Lotus").unwrap();
+        assert_eq!(lotus.layout, "normal");
+        assert_eq!(lotus.faces.len(), 1);
+        assert_eq!(lotus.full_name(), "Black Lotus");
+    }
+
+    #[test]
+    fn test_aliases() {
+        let mut conn = init_test_db_with_test_cards();
+        // A made up Godzilla series style reprint of Nissa
+        import_test_card_with(&mut conn, "nissa.json", |reprint| {
+            reprint["id"] = "5d1e8d5a-0bc1-4ec9-9f6b-e4d0ee3c01c2".into();
+            reprint["set"] = "sld".into();
+            reprint["collector_number"] = "9999".into();
+            reprint["flavor_name"] = "Gaea's Gardener".into();
+            reprint["printed_name"] = "Nissa, Resurgent Animist".into();
+        });
+
+        assert_eq!(
+            card_aliases(&conn, "Nissa, Resurgent Animist"),
+            vec!["Gaea's Gardener"]
+        );
+        assert_eq!(
+            names_matching(&conn, "gardener"),
+            vec!["Nissa, Resurgent Animist"]
+        );
+        assert_eq!(
+            names_matching(&conn, "!\"gaea's gardener\""),
+            vec!["Nissa, Resurgent Animist"]
+        );
+        assert_eq!(
+            card_by_name(&conn, "Gaea's Gardener").unwrap().name,
+            "Nissa, Resurgent Animist"
+        );
+        assert!(card_aliases(&conn, "Little Girl").is_empty());
+    }
+
+    #[test]
+    fn test_is_queries() {
+        let mut conn = init_test_db_with_test_cards();
+        assert_eq!(names_matching(&conn, "is:reserved"), vec!["Black Lotus"]);
+        assert_eq!(names_matching(&conn, "is:digital"), vec!["Black Lotus"]);
+        assert_eq!(names_matching(&conn, "is:funny"), vec!["Little Girl"]);
+        assert_eq!(
+            names_matching(&conn, "is:commander"),
+            vec!["Nissa, Resurgent Animist"]
+        );
+        assert_eq!(
+            names_matching(&conn, "is:permanent"),
+            vec!["Black Lotus", "Little Girl", "Nissa, Resurgent Animist"]
+        );
+        assert_eq!(
+            names_matching(&conn, "is:spell -is:funny"),
+            vec!["Black Lotus", "Nissa, Resurgent Animist"]
+        );
+        assert!(
+            names_matching(
+                &conn,
+                "is:gamechanger or is:promo or is:fullart or is:textless or is:dfc or is:split or is:adventure"
+            )
+            .is_empty()
+        );
+        assert!(query_cards(&conn, &parse_query("is:shiny").unwrap()).is_err());
+
+        import_test_card_with(&mut conn, "black_lotus.json", |card| {
+            card["id"] = "e2a4b6c8-1d3f-4a5b-8c7d-9e0f1a2b3c4d".into();
+            card["name"] = "Seat of the Synod".into();
+            card["type_line"] = "Artifact Land".into();
+            card["scryfall_uri"] = "https://scryfall.com/card/mrd/282/seat-of-the-synod".into();
+        });
+        assert!(names_matching(&conn, "is:spell synod").is_empty());
+        assert_eq!(
+            names_matching(&conn, "is:permanent synod"),
+            vec!["Seat of the Synod"]
+        );
+    }
+
+    #[test]
+    fn test_query_ordering() {
+        let conn = init_test_db_with_test_cards();
+        // In the order they come back, unlike names_matching
+        let ordered =
+            |query: &str| names(query_cards(&conn, &parse_query(query).unwrap()).unwrap());
+        assert_eq!(
+            ordered("t:creature or t:artifact"),
+            vec!["Black Lotus", "Little Girl", "Nissa, Resurgent Animist"]
+        );
+        assert_eq!(
+            ordered("order:mv direction:desc"),
+            vec!["Nissa, Resurgent Animist", "Little Girl", "Black Lotus"]
+        );
+        assert_eq!(
+            ordered("order:released"),
+            vec!["Nissa, Resurgent Animist", "Black Lotus", "Little Girl"]
+        );
+        assert_eq!(
+            ordered("order:rarity"),
+            vec!["Black Lotus", "Nissa, Resurgent Animist", "Little Girl"]
+        );
+        // Black Lotus only has a tix price, so goes at the end
+        assert_eq!(
+            ordered("order:usd direction:desc"),
+            vec!["Nissa, Resurgent Animist", "Little Girl", "Black Lotus"]
+        );
+        assert_eq!(
+            ordered("t:creature order:pow"),
+            vec!["Little Girl", "Nissa, Resurgent Animist"]
+        );
+        assert!(query_cards(&conn, &parse_query("order:colour").unwrap()).is_err());
+        assert!(query_cards(&conn, &parse_query("t:elf or order:mv").unwrap()).is_err());
+    }
+
+    #[test]
+    fn test_type_queries() {
+        let conn = init_test_db_with_test_cards();
+        assert_eq!(
+            names_matching(&conn, "t:legendary t:elf -t:token"),
+            vec!["Nissa, Resurgent Animist"]
+        );
+        assert_eq!(
+            names_matching(&conn, "t:\"creature human\""),
+            vec!["Little Girl"]
+        );
+        // Substrings of types shouldn't count
+        assert!(names_matching(&conn, "t:crea").is_empty());
+        assert!(names_matching(&conn, "subtype:creature").is_empty());
+        assert_eq!(
+            names_matching(&conn, "supertype:legendary"),
+            vec!["Nissa, Resurgent Animist"]
+        );
+    }
+
+    #[test]
+    fn test_oracle_text_search() {
+        let conn = init_test_db_with_test_cards();
+        let oracle_names = |text: &str| names(search_oracle_text(&conn, text));
+        assert_eq!(
+            oracle_names("\"land you control\""),
+            vec!["Nissa, Resurgent Animist"]
+        );
+        assert_eq!(oracle_names("revea*"), vec!["Nissa, Resurgent Animist"]);
+        assert!(oracle_names("\"control you land\"").is_empty());
+        assert_eq!(
+            names_matching(&conn, "o:\"whenever a land\""),
+            vec!["Nissa, Resurgent Animist"]
+        );
+        assert!(names_matching(&conn, "o:lan").is_empty());
+
+        // Little Girl has no oracle text, and the placeholder shown for it isn't searchable
+        assert!(oracle_names("\"no oracle text\"").is_empty());
+        assert!(names_matching(&conn, "o:oracle").is_empty());
+        let little_girl = card_by_name(&conn, "Little Girl").unwrap();
+        assert_eq!(little_girl.faces[0].oracle_text, None);
+        assert!(little_girl.to_string().contains("<No Oracle Text>"));
     }
 
     #[test]
@@ -589,29 +2613,13 @@ mod tests {
             "You need to download the default-cards-... file from Scryfall bulk data. Can be found here: https://scryfall.com/docs/api/bulk-data and rename to default-cards.json"
         );
         update_db_with_file(f, &mut conn);
-        let sql = "SELECT scryfall_uuid, oracle_uuid, name, type_line, oracle_text, power_toughness, loyalty, mana_cost, scryfall_uri, oc_name, oc_type_line, oc_oracle_text, oc_power_toughness, oc_loyalty, oc_mana_cost, set_name, released_at
-             FROM cards WHERE name = (?1)";
-        let mut stmt = conn.prepare(sql).unwrap();
+        let sql = format!("SELECT {} FROM cards WHERE name = (?1)", DB_CARD_COLUMNS);
+        let mut stmt = conn.prepare(&sql).unwrap();
         let mut rows = stmt.query(["Black Lotus"]).unwrap();
-        let card = rows.next().unwrap().map(|row| DbCard {
-            scryfall_uuid: row.get(0).unwrap(),
-            oracle_uuid: row.get(1).unwrap(),
-            name: row.get(2).unwrap(),
-            type_line: row.get(3).unwrap(),
-            oracle_text: row.get(4).unwrap(),
-            power_toughness: row.get(5).unwrap(),
-            loyalty: row.get(6).unwrap(),
-            mana_cost: row.get(7).unwrap(),
-            scryfall_uri: row.get(8).unwrap(),
-            oc_name: row.get(9).unwrap(),
-            oc_type_line: row.get(10).unwrap(),
-            oc_oracle_text: row.get(11).unwrap(),
-            oc_power_toughness: row.get(12).unwrap(),
-            oc_loyalty: row.get(13).unwrap(),
-            oc_mana_cost: row.get(14).unwrap(),
-            set_name: row.get(15).unwrap(),
-            released_at: row.get(16).unwrap(),
-        });
+        let card = rows
+            .next()
+            .unwrap()
+            .map(|row| row_to_db_card(&conn, row).unwrap());
         dbg!(card);
     }
 }
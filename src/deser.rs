@@ -1,5 +1,6 @@
+use super::mana::ManaValue;
 use chrono::NaiveDate;
-use serde::Deserialize;
+use serde::{Deserialize, Deserializer};
 use serde_json::Value;
 use uuid::Uuid;
 
@@ -46,7 +47,7 @@ pub struct ScryfallCard {
 
     // NOTE: Much of the next  is a repeat of what's in the ScryfallCardFaceObject if you change something here, change something there
     // NOTE: Probably a bad idea to rename color -> colour just for the sake
-    pub cmc: Option<f64>, // TODO: Make this a proper Decimal - see "Little Girl" card for example of cmc of 0.5
+    pub cmc: Option<ManaValue>, // See "Little Girl" card for example of cmc of 0.5
     #[serde(rename = "color_identity")]
     pub colour_identity: Option<Vec<Colour>>,
     #[serde(rename = "color_indicator")]
@@ -135,7 +136,7 @@ pub struct ScryfallCard {
 pub struct ScryfallCardFaceObject {
     pub artist: Option<String>,
     pub artist_id: Option<Uuid>, // UUID
-    pub cmc: Option<f64>, // TODO: Make this a proper Decimal - see "Little Girl" card for example of cmc of 0.5
+    pub cmc: Option<ManaValue>,  // See "Little Girl" card for example of cmc of 0.5
     #[serde(rename = "color_identity")]
     pub colour_identity: Option<Vec<Colour>>,
     #[serde(rename = "color_indicator")]
@@ -192,7 +193,7 @@ pub enum Colour {
     Tap,
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
 pub enum Legality {
     #[serde(rename = "legal")]
     Legal,
@@ -204,32 +205,71 @@ pub enum Legality {
     Restricted,
 }
 
-#[allow(dead_code)]
-#[derive(Deserialize, Debug)]
-pub struct FormatLegalities {
-    standard: Legality,
-    future: Legality,
-    historic: Legality,
-    timeless: Legality,
-    gladiator: Legality,
-    pioneer: Legality,
-    modern: Legality,
-    legacy: Legality,
-    pauper: Legality,
-    vintage: Legality,
-    penny: Legality,
-    commander: Legality,
-    oathbreaker: Legality,
-    standardbrawl: Legality,
-    brawl: Legality,
-    alchemy: Legality,
-    paupercommander: Legality,
-    duel: Legality,
-    oldschool: Legality,
-    premodern: Legality,
-    predh: Legality,
+impl Legality {
+    // Same as Scryfall's names, which is also how they're stored in the database
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Legality::Legal => "legal",
+            Legality::NotLegal => "not_legal",
+            Legality::Banned => "banned",
+            Legality::Restricted => "restricted",
+        }
+    }
+
+    pub fn from_db_str(legality: &str) -> Option<Legality> {
+        match legality {
+            "legal" => Some(Legality::Legal),
+            "not_legal" => Some(Legality::NotLegal),
+            "banned" => Some(Legality::Banned),
+            "restricted" => Some(Legality::Restricted),
+            _ => None,
+        }
+    }
+}
+
+// The struct, the list of names and all() come from the one list so they can't disagree
+macro_rules! format_legalities {
+    ($($format:ident),* $(,)?) => {
+        // In the same order as FormatLegalities
+        pub const FORMAT_NAMES: &[&str] = &[$(stringify!($format)),*];
+
+        #[derive(Deserialize, Debug)]
+        pub struct FormatLegalities {
+            $(pub $format: Legality,)*
+        }
+
+        impl FormatLegalities {
+            pub fn all(&self) -> [(&'static str, Legality); FORMAT_NAMES.len()] {
+                [$((stringify!($format), self.$format)),*]
+            }
+        }
+    };
 }
 
+format_legalities!(
+    standard,
+    future,
+    historic,
+    timeless,
+    gladiator,
+    pioneer,
+    modern,
+    legacy,
+    pauper,
+    vintage,
+    penny,
+    commander,
+    oathbreaker,
+    standardbrawl,
+    brawl,
+    alchemy,
+    paupercommander,
+    duel,
+    oldschool,
+    premodern,
+    predh,
+);
+
 #[allow(dead_code)]
 #[derive(Deserialize, Debug)]
 pub enum BorderColour {
@@ -378,19 +418,33 @@ pub struct Preview {
     pub source: Option<String>,
 }
 
-#[allow(dead_code)]
+// Scryfall sends prices as strings like "0.31"
+fn deserialize_price<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<f64>, D::Error> {
+    let price: Option<String> = Option::deserialize(deserializer)?;
+    price
+        .map(|p| p.parse().map_err(serde::de::Error::custom))
+        .transpose()
+}
+
 #[derive(Deserialize, Debug)]
 pub struct Prices {
-    usd: Option<String>, // TODO Convert to f64?
-    usd_foil: Option<String>,
-    usd_etched: Option<String>,
-    eur: Option<String>,
-    eur_foil: Option<String>,
-    tix: Option<String>,
+    #[serde(deserialize_with = "deserialize_price")]
+    pub usd: Option<f64>,
+    #[serde(deserialize_with = "deserialize_price")]
+    pub usd_foil: Option<f64>,
+    #[serde(deserialize_with = "deserialize_price")]
+    pub usd_etched: Option<f64>,
+    #[serde(deserialize_with = "deserialize_price")]
+    pub eur: Option<f64>,
+    #[serde(deserialize_with = "deserialize_price")]
+    pub eur_foil: Option<f64>,
+    #[serde(deserialize_with = "deserialize_price")]
+    pub tix: Option<f64>,
 }
 
+// In Scryfall's order, so r>=rare works
 #[allow(dead_code)]
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
 pub enum Rarity {
     #[serde(rename = "common")]
     Common,
@@ -406,6 +460,36 @@ pub enum Rarity {
     Bonus,
 }
 
+impl Rarity {
+    pub const ALL: [Rarity; 6] = [
+        Rarity::Common,
+        Rarity::Uncommon,
+        Rarity::Rare,
+        Rarity::Special,
+        Rarity::Mythic,
+        Rarity::Bonus,
+    ];
+
+    // Takes the names and their first letters, like Scryfall does
+    pub fn parse(rarity: &str) -> Option<Rarity> {
+        let rarity = rarity.to_lowercase();
+        Rarity::ALL
+            .into_iter()
+            .find(|r| r.as_str() == rarity || r.as_str()[..1] == rarity)
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Rarity::Common => "common",
+            Rarity::Uncommon => "uncommon",
+            Rarity::Rare => "rare",
+            Rarity::Special => "special",
+            Rarity::Mythic => "mythic",
+            Rarity::Bonus => "bonus",
+        }
+    }
+}
+
 #[allow(dead_code)]
 #[derive(Deserialize, Debug)]
 pub struct PurchaseUris {
@@ -495,6 +579,37 @@ pub enum SetType {
     Vanguard,
 }
 
+impl SetType {
+    // See Legality::as_str
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SetType::Alchemy => "alchemy",
+            SetType::Archenemy => "archenemy",
+            SetType::Arsenal => "arsenal",
+            SetType::Box => "box",
+            SetType::Commander => "commander",
+            SetType::Core => "core",
+            SetType::DraftInnovation => "draft_innovation",
+            SetType::DuelDeck => "duel_deck",
+            SetType::Expansion => "expansion",
+            SetType::FromTheVault => "from_the_vault",
+            SetType::Funny => "funny",
+            SetType::Masterpiece => "masterpiece",
+            SetType::Masters => "masters",
+            SetType::Memorabilia => "memorabilia",
+            SetType::Minigame => "minigame",
+            SetType::Planechase => "planechase",
+            SetType::PremiumDeck => "premium_deck",
+            SetType::Promo => "promo",
+            SetType::SpellBook => "spellbook",
+            SetType::Starter => "starter",
+            SetType::Token => "token",
+            SetType::TreasureChest => "treasure_chest",
+            SetType::Vanguard => "vanguard",
+        }
+    }
+}
+
 // TODO Complete this
 #[allow(dead_code)]
 #[derive(Deserialize, Debug)]
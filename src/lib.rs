@@ -1,14 +1,52 @@
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashSet};
+use std::iter;
+
 mod deser;
-pub use deser::{ScryfallCard, weird_cards};
+pub use deser::{FORMAT_NAMES, FormatLegalities, Legality, ScryfallCard, weird_cards};
+
+mod colour;
+pub use colour::{ColourSearch, Colours, parse_colour_search};
 
 mod db;
 pub use db::{
-    DbCard, DbExistanceErrors, check_db_exists_and_populated, find_matching_cards,
-    find_matching_cards_scryfall_style, get_all_card_names, get_all_mtg_words,
-    get_all_names_for_card, get_card_by_name, get_db_connection, init_db,
-    percentage_search_strings, update_db_with_file,
+    Currency, DbCard, DbExistanceErrors, DbPrint, DbSet, Face, add_nickname, any_cards_match_query,
+    check_db_exists_and_populated, find_cards_by_oracle_text, find_cards_matching_query,
+    find_matching_cards, find_matching_cards_scryfall_style, get_all_card_names, get_all_keywords,
+    get_all_mtg_words, get_all_names_for_card, get_all_nicknames, get_all_sets, get_card_by_name,
+    get_card_name_completions, get_card_names_with_words, get_cards_for_initialism,
+    get_cards_for_nickname, get_cheapest_print_for_card, get_close_mtg_words, get_db_connection,
+    get_keywords_for_card, get_legalities_for_card, get_print_by_set_and_number,
+    get_prints_by_artist, get_prints_for_card, init_db, percentage_search_strings, remove_nickname,
+    update_db_with_file,
+};
+
+mod mana;
+pub use mana::{ManaSymbol, ManaValue, parse_mana_cost};
+
+mod nickname;
+pub use nickname::{
+    BUNDLED_NICKNAMES, NicknameError, name_initialisms, nicknames_to_tsv, parse_nickname_tsv,
 };
 
+mod normalise;
+pub use normalise::normalise_search_text;
+
+mod query;
+pub use query::{
+    Direction, Operator, Query, QueryError, SortOrder, SqlFilter, compile_query, join_search_args,
+    parse_query,
+};
+
+mod rank;
+pub use rank::{ScoredCard, name_similarity, rank_cards, score_card_name, score_cards};
+
+mod stats;
+pub use stats::parse_stat;
+
+mod type_line;
+pub use type_line::{TypeKind, TypeLine, parse_type_line};
+
 mod utils;
 pub use utils::{create_local_data_folder, get_local_data_folder, get_local_data_sqlite_file};
 
@@ -18,100 +56,343 @@ pub use download::download_omenpath_set;
 #[derive(Debug)]
 pub enum CardMatchResult {
     DidYouMean(Vec<String>, Vec<String>),
-    MultipleCardsMatch(Vec<DbCard>),
+    // Whole card names that are close to the search, best first
+    CloseCardNames(Vec<String>),
+    // Whole searches with every misspelled word fixed, each of which finds at least one card
+    CorrectedQueries(Vec<String>),
+    MultipleCardsMatch(Vec<ScoredCard>),
     ExactCardFound(Box<DbCard>),
+    InvalidQuery(QueryError),
 }
 
-use textdistance::str::damerau_levenshtein;
+// How many typos a word can have and still be suggested
+pub const DEFAULT_SPELLING_DISTANCE: usize = 2;
 
 pub fn find_magic_words_with_close_spelling(
     search_text: &Vec<String>,
+    max_distance: usize,
 ) -> (Vec<(usize, String)>, Vec<String>) {
-    let mtg_words = get_all_mtg_words();
     let mut close_words = Vec::new();
     let mut exact_words = Vec::new();
     for search_string in search_text {
-        let mut skip_word = false;
-        let mut close_names_for_current_word = Vec::new();
-        for mtg_card_name in &mtg_words {
-            let dist = damerau_levenshtein(search_string, mtg_card_name);
-            if dist == 0 {
-                // Skip words that are already matching
-                skip_word = true;
-                break;
-            }
-            if dist <= 2 {
-                close_names_for_current_word.push((dist, mtg_card_name.clone()));
-            }
-        }
-        if !skip_word {
-            close_words.extend(close_names_for_current_word);
-        } else {
+        let close_names_for_current_word = get_close_mtg_words(search_string, max_distance);
+        // Skip words that are already matching
+        if close_names_for_current_word
+            .iter()
+            .any(|(dist, _)| *dist == 0)
+        {
             exact_words.push(search_string.to_string());
+        } else {
+            close_words.extend(close_names_for_current_word);
         }
     }
     close_words.sort_by_key(|k| k.0);
     (close_words, exact_words)
 }
 
-pub fn try_find_card_with_nickname(search_string: &str) -> Option<&str> {
-    // TODO fill this out more and maybe move to a different file or something
-    //  Look here for some more common names: https://mtg.fandom.com/wiki/List_of_Magic_slang/Card_nicknames
-    //  Don't look too long though. Fandom sucks
-    let card_nicknames = vec![
-        ("bob", "Dark Confidant"),
-        ("academy", "Tolarian Academy"),
-        ("ak", "Accumulated Knowledge"),
-        ("ancestral", "Ancestral Recall"),
-        ("k command", "Kolaghan's Command"),
-        ("kcommand", "Kolaghan's Command"),
-        ("jvp", "Jace, Vryn's Prodigy"),
-        ("nathan", "Duelist of the Mind"), // This does mean Nathan Drake is harder to search for - fuck that guy
-    ];
-    let lower_name = search_string.trim().to_lowercase();
-    for (card_nickname, card_name) in card_nicknames {
-        if card_nickname == lower_name {
-            return Some(card_name);
+// Anything less alike than this (see name_similarity) isn't worth suggesting
+const CLOSE_NAME_THRESHOLD: f64 = 0.6;
+const MAX_CLOSE_NAMES: usize = 10;
+
+// Card names that are close to the search words as a whole, best first. Copes with several typos
+//  at once, missing apostrophes and words in the wrong order.
+pub fn find_cards_with_close_names(search_words: &[String], max_distance: usize) -> Vec<String> {
+    let mut candidate_words = Vec::new();
+    for search_word in search_words {
+        candidate_words.push(search_word.replace('\'', ""));
+        candidate_words.extend(
+            get_close_mtg_words(search_word, max_distance)
+                .into_iter()
+                .map(|(_, word)| word),
+        );
+    }
+    let search = search_words.join(" ");
+    // A card is as close as its closest face. The faces of a card come one after the other.
+    let mut close_names: Vec<(f64, String)> = Vec::new();
+    for (name, face_name) in get_card_names_with_words(&candidate_words) {
+        let similarity = name_similarity(&search, &face_name, max_distance);
+        match close_names.last_mut() {
+            Some((best, last_name)) if *last_name == name => *best = best.max(similarity),
+            _ => close_names.push((similarity, name)),
         }
     }
-    None
+    close_names.retain(|(similarity, _)| *similarity >= CLOSE_NAME_THRESHOLD);
+    close_names.sort_by(|a, b| b.0.total_cmp(&a.0));
+    close_names
+        .into_iter()
+        .take(MAX_CLOSE_NAMES)
+        .map(|(_, name)| name)
+        .collect()
 }
 
-pub fn try_match_card(search_text: &Vec<String>) -> CardMatchResult {
-    let percentaged_search_text = percentage_search_strings(search_text);
-    let mut matching_cards = find_matching_cards_scryfall_style(&percentaged_search_text);
+// Only this many of the closest words are tried for each misspelled word
+const MAX_CORRECTIONS_PER_WORD: usize = 3;
+// Every search tried is a trip to the db, so there's a limit
+const MAX_CORRECTED_SEARCHES_TRIED: usize = 64;
+const MAX_CORRECTED_QUERIES: usize = 10;
+
+// Fixes every misspelled name word at once, trying combinations of close words (fewest typos
+//  first) and keeping the searches that find something. The rest of the query is kept as is.
+pub fn find_corrected_queries(query: &Query, max_distance: usize) -> Vec<String> {
+    let mut corrections_per_word = Vec::new();
+    let mut any_misspelled = false;
+    for word in query.name_words() {
+        let close_words = get_close_mtg_words(&word, max_distance);
+        // Words without anything close might just be part of a word, so they stay
+        if close_words.is_empty() || close_words.iter().any(|(dist, _)| *dist == 0) {
+            corrections_per_word.push(vec![(0, word)]);
+        } else {
+            any_misspelled = true;
+            corrections_per_word.push(
+                close_words
+                    .into_iter()
+                    .take(MAX_CORRECTIONS_PER_WORD)
+                    .collect(),
+            );
+        }
+    }
+    if !any_misspelled {
+        return Vec::new();
+    }
+
+    let mut corrected_queries = Vec::new();
+    for words in combinations_by_distance(&corrections_per_word).take(MAX_CORRECTED_SEARCHES_TRIED)
+    {
+        let corrected_query = query.with_name_words(&words);
+        if let Ok(true) = any_cards_match_query(&corrected_query) {
+            corrected_queries.push(corrected_query.to_string());
+            if corrected_queries.len() == MAX_CORRECTED_QUERIES {
+                break;
+            }
+        }
+    }
+    corrected_queries
+}
+
+// Every way of picking one word for each slot, lowest total distance first. Only makes the next
+//  combination when it's asked for, as there are a lot of them with a few misspelled words. Each
+//  slot's words have to be sorted by distance.
+fn combinations_by_distance(
+    slots: &[Vec<(usize, String)>],
+) -> impl Iterator<Item = Vec<String>> + '_ {
+    let total = move |picks: &[usize]| -> usize {
+        picks
+            .iter()
+            .zip(slots)
+            .map(|(&pick, words)| words[pick].0)
+            .sum()
+    };
+    // Picks are the index of the word used in each slot
+    let mut queue = BinaryHeap::new();
+    let mut seen = HashSet::new();
+    if slots.iter().all(|words| !words.is_empty()) {
+        let first = vec![0; slots.len()];
+        queue.push(Reverse((total(&first), first.clone())));
+        seen.insert(first);
+    }
+    iter::from_fn(move || {
+        let Reverse((_, picks)) = queue.pop()?;
+        for slot in 0..picks.len() {
+            if picks[slot] + 1 < slots[slot].len() {
+                let mut next = picks.clone();
+                next[slot] += 1;
+                if seen.insert(next.clone()) {
+                    queue.push(Reverse((total(&next), next)));
+                }
+            }
+        }
+        Some(
+            picks
+                .iter()
+                .zip(slots)
+                .map(|(&pick, words)| words[pick].1.clone())
+                .collect(),
+        )
+    })
+}
+
+// Longer than this and it's probably not someone's initials
+const MAX_INITIALISM_LENGTH: usize = 6;
+
+pub fn try_match_card(search_text: &str) -> CardMatchResult {
+    try_match_card_with_spelling_distance(search_text, DEFAULT_SPELLING_DISTANCE)
+}
+
+// The cards a nickname or initialism is for, then the rest of the search's matches
+fn cards_first(
+    first: Vec<ScoredCard>,
+    matching_cards: Vec<DbCard>,
+    name_words: &[String],
+) -> CardMatchResult {
+    let other_cards = matching_cards
+        .into_iter()
+        .filter(|card| !first.iter().any(|scored| scored.card == *card))
+        .collect();
+    let mut cards = first;
+    cards.extend(rank_cards(other_cards, name_words));
+    if cards.len() == 1 {
+        return CardMatchResult::ExactCardFound(Box::new(cards.remove(0).card));
+    }
+    CardMatchResult::MultipleCardsMatch(cards)
+}
+
+// max_distance is how many typos a word can have for the DidYouMean suggestions
+pub fn try_match_card_with_spelling_distance(
+    search_text: &str,
+    max_distance: usize,
+) -> CardMatchResult {
+    let query = match parse_query(search_text) {
+        Ok(q) => q,
+        Err(e) => return CardMatchResult::InvalidQuery(e),
+    };
+    let matching_cards = match find_cards_matching_query(&query) {
+        Ok(cards) => cards,
+        Err(e) => return CardMatchResult::InvalidQuery(e),
+    };
+
+    // Nicknames come first - "bob" is Dark Confidant, then every card with "bob" in its name
+    let nickname_cards = get_cards_for_nickname(search_text);
+    if !nickname_cards.is_empty() {
+        return cards_first(
+            rank_cards(nickname_cards, &[]),
+            matching_cards,
+            &query.name_words(),
+        );
+    }
+
+    // A short word that isn't a whole word in any card name could be initials, like "jvp" for
+    //  Jace, Vryn's Prodigy. Those cards go first (most popular first), then anything with the
+    //  word in its name.
+    if let Query::Name(word) = &query
+        && (2..=MAX_INITIALISM_LENGTH).contains(&word.chars().count())
+        && !word.contains(char::is_whitespace)
+        && get_close_mtg_words(word, 0).is_empty()
+    {
+        let initialism_cards = get_cards_for_initialism(word);
+        if !initialism_cards.is_empty() {
+            let name_words = query.name_words();
+            return cards_first(
+                score_cards(initialism_cards, &name_words),
+                matching_cards,
+                &name_words,
+            );
+        }
+    }
 
     if matching_cards.is_empty() {
-        let (close_names, exact_card_names) = find_magic_words_with_close_spelling(search_text);
+        // Only when the search is just a name - there's no telling if a close card would pass
+        //  the rest of the search
+        if query.without_names().is_none() {
+            let close_card_names = find_cards_with_close_names(&query.name_words(), max_distance);
+            if !close_card_names.is_empty() {
+                return CardMatchResult::CloseCardNames(close_card_names);
+            }
+        }
+        let corrected_queries = find_corrected_queries(&query, max_distance);
+        if !corrected_queries.is_empty() {
+            return CardMatchResult::CorrectedQueries(corrected_queries);
+        }
+        let (close_names, mut exact_card_names) =
+            find_magic_words_with_close_spelling(&query.name_words(), max_distance);
+        // Hang on to the rest of the query so a re-search with the close word still has it
+        if let Some(filters) = query.without_names() {
+            exact_card_names.push(filters.to_string());
+        }
         let (_, close_card_names): (Vec<usize>, Vec<String>) = close_names.into_iter().unzip();
         CardMatchResult::DidYouMean(close_card_names, exact_card_names)
     } else if matching_cards.len() == 1 {
         let card = get_card_by_name(&matching_cards[0].name).unwrap();
         CardMatchResult::ExactCardFound(Box::new(card))
     } else {
-        matching_cards.sort();
-        CardMatchResult::MultipleCardsMatch(matching_cards)
+        let name_words = query.name_words();
+        // Searches without any name words stay in name order, and an order: given in the search
+        //  beats relevance
+        if name_words.is_empty() || query.has_ordering() {
+            CardMatchResult::MultipleCardsMatch(score_cards(matching_cards, &name_words))
+        } else {
+            CardMatchResult::MultipleCardsMatch(rank_cards(matching_cards, &name_words))
+        }
     }
 }
 
 pub fn get_display_string(card: &DbCard) -> String {
-    let mut display_string = match card.oc_name {
-        Some(ref _c) => {
-            let mut display_string = String::new();
-            display_string.push_str(&card.to_string());
-            display_string
+    let mut display_string = card.to_string();
+    let keywords = get_keywords_for_card(card);
+    if !keywords.is_empty() {
+        display_string.push_str(&format!("\nKeywords: {}", keywords.join(", ")));
+    }
+    let legalities = get_legalities_for_card(card);
+    for (label, legality) in [
+        ("Legal in", Legality::Legal),
+        ("Restricted in", Legality::Restricted),
+        ("Banned in", Legality::Banned),
+    ] {
+        let formats: Vec<&str> = legalities
+            .iter()
+            .filter(|(_, l)| *l == legality)
+            .map(|(format, _)| format.as_str())
+            .collect();
+        if !formats.is_empty() {
+            display_string.push_str(&format!("\n{}: {}", label, formats.join(", ")));
         }
-        None => card.to_string(),
-    };
+    }
+    let cheapest: Vec<String> = Currency::ALL
+        .iter()
+        .filter_map(|currency| {
+            get_cheapest_print_for_card(card, *currency).map(|(print, price)| {
+                format!(
+                    "{} ({})",
+                    currency.format(price),
+                    print.set_code.to_uppercase()
+                )
+            })
+        })
+        .collect();
+    if !cheapest.is_empty() {
+        display_string.push_str(&format!("\nCheapest: {}", cheapest.join(", ")));
+    }
     let names_for_card = get_all_names_for_card(card);
-    if names_for_card.len() > 1 {
-        display_string.push_str("\nThis card is also known as:");
-        for card_name in names_for_card {
-            if card_name.contains(&card.name) {
-                continue;
-            }
-            display_string.push_str(&format!(" {}", card_name).to_string());
-        }
+    if !names_for_card.is_empty() {
+        display_string.push_str(&format!(
+            "\nThis card is also known as: {}",
+            names_for_card.join(", ")
+        ));
     }
     display_string
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn slot(words: &[(usize, &str)]) -> Vec<(usize, String)> {
+        words.iter().map(|(d, w)| (*d, w.to_string())).collect()
+    }
+
+    #[test]
+    fn test_combinations_by_distance() {
+        let slots = vec![
+            slot(&[(1, "black"), (2, "block")]),
+            slot(&[(0, "lotus")]),
+            slot(&[(1, "girl"), (1, "gill"), (2, "grill")]),
+        ];
+        let combinations: Vec<String> = combinations_by_distance(&slots)
+            .map(|words| words.join(" "))
+            .collect();
+        assert_eq!(
+            combinations,
+            vec![
+                "black lotus girl",
+                "black lotus gill",
+                "black lotus grill",
+                "block lotus girl",
+                "block lotus gill",
+                "block lotus grill",
+            ]
+        );
+
+        // Twenty misspelled words would be billions of combinations if they were all made
+        let slots = vec![slot(&[(1, "a"), (1, "b"), (2, "c")]); 20];
+        assert_eq!(combinations_by_distance(&slots).take(64).count(), 64);
+    }
+}
@@ -0,0 +1,221 @@
+use serde::Deserialize;
+use std::fmt;
+
+// Mana values are always whole or half numbers (thanks Little Girl), so keep them as a count of
+//  halves rather than trusting a float to stay exact
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+#[serde(from = "f64")]
+pub struct ManaValue {
+    halves: u64,
+}
+
+impl From<f64> for ManaValue {
+    fn from(value: f64) -> Self {
+        ManaValue {
+            halves: (value * 2.0).round() as u64,
+        }
+    }
+}
+
+impl ManaValue {
+    // Halves are exact in an f64, so this is safe to store and compare
+    pub fn as_f64(&self) -> f64 {
+        self.halves as f64 / 2.0
+    }
+}
+
+impl fmt::Display for ManaValue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.halves.is_multiple_of(2) {
+            write!(f, "{}", self.halves / 2)
+        } else {
+            write!(f, "{}.5", self.halves / 2)
+        }
+    }
+}
+
+// https://scryfall.com/docs/api/card-symbols
+#[derive(Debug, Clone, PartialEq)]
+pub enum ManaSymbol {
+    Generic(u32),
+    // X, Y, and Z
+    Variable(char),
+    Coloured(char),
+    Colourless,
+    Snow,
+    // {W/U}
+    Hybrid(char, char),
+    // {2/W}
+    MonoHybrid(char),
+    // {W/P}
+    Phyrexian(char),
+    // {W/U/P}
+    HybridPhyrexian(char, char),
+    // {HW}
+    Half(char),
+    // Anything else, like {∞} or {½}
+    Other(String),
+}
+
+fn is_colour(c: char) -> bool {
+    "WUBRG".contains(c)
+}
+
+impl ManaSymbol {
+    fn parse(text: &str) -> ManaSymbol {
+        let text = text.to_uppercase();
+        let parts: Vec<&str> = text.split('/').collect();
+        let single = |part: &str| -> Option<char> {
+            let mut chars = part.chars();
+            let c = chars.next()?;
+            if chars.next().is_none() {
+                Some(c)
+            } else {
+                None
+            }
+        };
+        match parts.as_slice() {
+            [part] => {
+                if let Ok(generic) = part.parse::<u32>() {
+                    return ManaSymbol::Generic(generic);
+                }
+                match (single(part), part.strip_prefix('H').and_then(single)) {
+                    (Some(c), _) if is_colour(c) => ManaSymbol::Coloured(c),
+                    (Some('C'), _) => ManaSymbol::Colourless,
+                    (Some('S'), _) => ManaSymbol::Snow,
+                    (Some(c), _) if "XYZ".contains(c) => ManaSymbol::Variable(c),
+                    (_, Some(c)) if is_colour(c) => ManaSymbol::Half(c),
+                    _ => ManaSymbol::Other(text.clone()),
+                }
+            }
+            [first, second] => match (single(first), single(second)) {
+                (Some(a), Some('P')) if is_colour(a) => ManaSymbol::Phyrexian(a),
+                (Some(a), Some(b)) if is_colour(a) && is_colour(b) => ManaSymbol::Hybrid(a, b),
+                (Some('2'), Some(b)) if is_colour(b) => ManaSymbol::MonoHybrid(b),
+                // {C/W} and friends from the Final Fantasy set
+                (Some('C'), Some(b)) if is_colour(b) => ManaSymbol::Hybrid('C', b),
+                _ => ManaSymbol::Other(text.clone()),
+            },
+            [first, second, third] => match (single(first), single(second), single(third)) {
+                (Some(a), Some(b), Some('P')) if is_colour(a) && is_colour(b) => {
+                    ManaSymbol::HybridPhyrexian(a, b)
+                }
+                _ => ManaSymbol::Other(text.clone()),
+            },
+            _ => ManaSymbol::Other(text.clone()),
+        }
+    }
+
+    pub fn is_hybrid(&self) -> bool {
+        matches!(
+            self,
+            ManaSymbol::Hybrid(_, _)
+                | ManaSymbol::MonoHybrid(_)
+                | ManaSymbol::HybridPhyrexian(_, _)
+        )
+    }
+
+    pub fn is_phyrexian(&self) -> bool {
+        matches!(
+            self,
+            ManaSymbol::Phyrexian(_) | ManaSymbol::HybridPhyrexian(_, _)
+        )
+    }
+}
+
+impl fmt::Display for ManaSymbol {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ManaSymbol::Generic(n) => write!(f, "{{{}}}", n),
+            ManaSymbol::Variable(c) | ManaSymbol::Coloured(c) => write!(f, "{{{}}}", c),
+            ManaSymbol::Colourless => write!(f, "{{C}}"),
+            ManaSymbol::Snow => write!(f, "{{S}}"),
+            ManaSymbol::Hybrid(a, b) => write!(f, "{{{}/{}}}", a, b),
+            ManaSymbol::MonoHybrid(c) => write!(f, "{{2/{}}}", c),
+            ManaSymbol::Phyrexian(c) => write!(f, "{{{}/P}}", c),
+            ManaSymbol::HybridPhyrexian(a, b) => write!(f, "{{{}/{}/P}}", a, b),
+            ManaSymbol::Half(c) => write!(f, "{{H{}}}", c),
+            ManaSymbol::Other(text) => write!(f, "{{{}}}", text),
+        }
+    }
+}
+
+// Parses a cost like {2}{W}{U/P}. Also takes the shorthand people type into searches, like 2WW
+pub fn parse_mana_cost(cost: &str) -> Vec<ManaSymbol> {
+    let mut symbols = Vec::new();
+    if cost.contains('{') {
+        for part in cost.split('{').skip(1) {
+            let text = part.split('}').next().unwrap_or_default();
+            symbols.push(ManaSymbol::parse(text));
+        }
+        return symbols;
+    }
+    let chars: Vec<char> = cost.chars().filter(|c| !c.is_whitespace()).collect();
+    let mut i = 0;
+    while i < chars.len() {
+        let start = i;
+        if chars[i].is_ascii_digit() {
+            while i < chars.len() && chars[i].is_ascii_digit() {
+                i += 1;
+            }
+        } else {
+            i += 1;
+        }
+        let text: String = chars[start..i].iter().collect();
+        symbols.push(ManaSymbol::parse(&text));
+    }
+    symbols
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn half_mana_value() {
+        let little_girl: ManaValue = serde_json::from_str("0.5").unwrap();
+        assert_eq!(little_girl.to_string(), "0.5");
+        assert_eq!(little_girl.as_f64(), 0.5);
+        let three: ManaValue = serde_json::from_str("3.0").unwrap();
+        assert_eq!(three.to_string(), "3");
+        assert!(little_girl < three);
+    }
+
+    #[test]
+    fn parse_costs() {
+        assert_eq!(
+            parse_mana_cost("{2}{W}{U/P}{X}"),
+            vec![
+                ManaSymbol::Generic(2),
+                ManaSymbol::Coloured('W'),
+                ManaSymbol::Phyrexian('U'),
+                ManaSymbol::Variable('X'),
+            ]
+        );
+        assert_eq!(
+            parse_mana_cost("{HW}{2/G}{G/U}{B/G/P}"),
+            vec![
+                ManaSymbol::Half('W'),
+                ManaSymbol::MonoHybrid('G'),
+                ManaSymbol::Hybrid('G', 'U'),
+                ManaSymbol::HybridPhyrexian('B', 'G'),
+            ]
+        );
+        assert_eq!(
+            parse_mana_cost("10rr"),
+            vec![
+                ManaSymbol::Generic(10),
+                ManaSymbol::Coloured('R'),
+                ManaSymbol::Coloured('R'),
+            ]
+        );
+    }
+
+    #[test]
+    fn hybrid_and_phyrexian() {
+        assert!(ManaSymbol::MonoHybrid('W').is_hybrid());
+        assert!(ManaSymbol::HybridPhyrexian('W', 'U').is_phyrexian());
+        assert!(!ManaSymbol::Coloured('W').is_hybrid());
+        assert_eq!(ManaSymbol::HybridPhyrexian('W', 'U').to_string(), "{W/U/P}");
+    }
+}
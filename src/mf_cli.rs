@@ -1,13 +1,30 @@
-use clap::Parser;
+use clap::{Parser, Subcommand};
+use magic_finder::CardMatchResult;
+use magic_finder::Currency;
+use magic_finder::DEFAULT_SPELLING_DISTANCE;
+use magic_finder::DbExistanceErrors;
+use magic_finder::add_nickname;
 use magic_finder::check_db_exists_and_populated;
+use magic_finder::find_cards_by_oracle_text;
+use magic_finder::get_all_keywords;
+use magic_finder::get_all_nicknames;
+use magic_finder::get_all_sets;
 use magic_finder::get_card_by_name;
+use magic_finder::get_card_name_completions;
+use magic_finder::get_cheapest_print_for_card;
 use magic_finder::get_db_connection;
 use magic_finder::get_local_data_folder;
+use magic_finder::get_prints_by_artist;
+use magic_finder::get_prints_for_card;
 use magic_finder::init_db;
-use magic_finder::try_match_card;
+use magic_finder::join_search_args;
+use magic_finder::nicknames_to_tsv;
+use magic_finder::normalise_search_text;
+use magic_finder::parse_nickname_tsv;
+use magic_finder::remove_nickname;
+use magic_finder::try_match_card_with_spelling_distance;
 use magic_finder::update_db_with_file;
-use magic_finder::CardMatchResult;
-use magic_finder::DbExistanceErrors;
+use std::fs;
 use std::path::PathBuf;
 use std::process::ExitCode;
 use std::process::Termination;
@@ -19,43 +36,203 @@ impl Termination for MtgCardExit {
         match self {
             MtgCardExit::NoExactMatchCard => ExitCode::from(102),
             MtgCardExit::DidYouMean => ExitCode::from(105),
+            MtgCardExit::CloseCardNames => ExitCode::from(107),
+            MtgCardExit::CorrectedQueries => ExitCode::from(108),
             MtgCardExit::MultipleCardsMatch => ExitCode::from(106),
             MtgCardExit::ExactCardFound => ExitCode::from(110),
             MtgCardExit::UpdateSuccess => ExitCode::from(120),
+            MtgCardExit::ListedSets => ExitCode::from(130),
+            MtgCardExit::ListedKeywords => ExitCode::from(131),
+            MtgCardExit::ListedArtistPrints => ExitCode::from(132),
+            MtgCardExit::ListedNicknames => ExitCode::from(133),
+            MtgCardExit::ChangedNicknames => ExitCode::from(134),
+            MtgCardExit::ListedCompletions => ExitCode::from(135),
             MtgCardExit::PrintedDatabaseFolder => ExitCode::from(150),
             MtgCardExit::DbError => ExitCode::from(201),
             MtgCardExit::EmptySearchString => ExitCode::from(202),
+            MtgCardExit::InvalidQuery => ExitCode::from(203),
+            MtgCardExit::NicknameError => ExitCode::from(204),
         }
     }
 }
 
 enum MtgCardExit {
     EmptySearchString,
+    InvalidQuery,
     NoExactMatchCard,
     DidYouMean,
+    CloseCardNames,
+    CorrectedQueries,
     MultipleCardsMatch,
     DbError,
     ExactCardFound,
     UpdateSuccess,
+    ListedSets,
+    ListedKeywords,
+    ListedArtistPrints,
+    ListedNicknames,
+    ChangedNicknames,
+    ListedCompletions,
+    NicknameError,
     PrintedDatabaseFolder,
 }
 
 #[derive(Parser, Debug)]
-#[command(version, about, long_about = None)]
+#[command(version, about, long_about = None, args_conflicts_with_subcommands = true)]
 struct Args {
+    #[command(subcommand)]
+    command: Option<Command>,
     /// Update the local db from given Scryfall bulk download
     #[arg(short, long)]
     update: Option<String>,
     /// Search for the exact string
     #[arg(short, long)]
     exact: bool,
+    /// Search the oracle text instead of the name, best matches first. "Quoted phrases" and
+    /// prefixes (exil*) work
+    #[arg(short, long)]
+    oracle: bool,
+    /// List every printing of the card, oldest first
+    #[arg(short, long)]
+    prints: bool,
+    /// Show the cheapest printing of the card in each currency
+    #[arg(short, long)]
+    cheapest: bool,
+    /// Sort matching cards by name, mv, released, rarity, usd, edhrec, penny, power, toughness or set
+    #[arg(long)]
+    order: Option<String>,
+    /// Sort matching cards asc or desc
+    #[arg(long)]
+    direction: Option<String>,
+    /// How many typos (at most 2) a word can have and still be suggested when nothing matches
+    #[arg(long, default_value_t = DEFAULT_SPELLING_DISTANCE)]
+    spelling_distance: usize,
     #[arg(short, long)]
     /// Print database folder (useful for debugging or deleting)
     database_folder: bool,
-    /// Text to search for card with
+    /// Text to search for card with. Supports Scryfall style searches like t:creature o:"draw a card" -c:u
+    /// Put -- first if it starts with a command's name, like -- complete disregard
+    #[arg(allow_hyphen_values = true)]
     search_text: Vec<String>,
 }
 
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// List every set in the database, oldest first
+    Sets,
+    /// List every keyword ability with how many cards have it
+    Keywords,
+    /// List every card illustrated by an artist, oldest first
+    Artist { name: Vec<String> },
+    /// List card names with words starting with the given prefix, one per line, for shell
+    /// completion and launchers
+    Complete {
+        prefix: Vec<String>,
+        /// The most names to list
+        #[arg(short, long, default_value_t = 10)]
+        limit: usize,
+    },
+    /// Add, remove, list, import or export card nicknames (like "bob" for Dark Confidant)
+    Nickname {
+        #[command(subcommand)]
+        action: NicknameCommand,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum NicknameCommand {
+    /// Add a nickname for a card, e.g. `nickname add bolt lightning bolt`. Quote nicknames with
+    /// spaces in them
+    Add {
+        nickname: String,
+        #[arg(required = true)]
+        card_name: Vec<String>,
+    },
+    /// Remove a nickname, or just one of the cards it means if a card name is given. Removed
+    /// bundled nicknames stay removed after an update
+    Remove {
+        nickname: String,
+        card_name: Vec<String>,
+    },
+    /// List every nickname and the card it means
+    List,
+    /// Add every nickname in a file with a nickname, a tab, then a card name on each line
+    Import { file: PathBuf },
+    /// Write every nickname to a file in the same format import reads
+    Export { file: PathBuf },
+}
+
+fn nickname_command(action: NicknameCommand) -> MtgCardExit {
+    match action {
+        NicknameCommand::Add {
+            nickname,
+            card_name,
+        } => match add_nickname(&nickname, &card_name.join(" ")) {
+            Ok(name) => {
+                println!("{}\t{}", normalise_search_text(&nickname), name);
+                MtgCardExit::ChangedNicknames
+            }
+            Err(e) => {
+                println!("{}", e);
+                MtgCardExit::NicknameError
+            }
+        },
+        NicknameCommand::Remove {
+            nickname,
+            card_name,
+        } => {
+            let card_name = card_name.join(" ");
+            let card_name = (!card_name.is_empty()).then_some(card_name.as_str());
+            match remove_nickname(&nickname, card_name) {
+                0 => {
+                    println!("There's no nickname \"{}\" to remove", nickname);
+                    MtgCardExit::NicknameError
+                }
+                removed => {
+                    println!("Removed {} nickname(s)", removed);
+                    MtgCardExit::ChangedNicknames
+                }
+            }
+        }
+        NicknameCommand::List => {
+            print!("{}", nicknames_to_tsv(&get_all_nicknames()));
+            MtgCardExit::ListedNicknames
+        }
+        NicknameCommand::Import { file } => {
+            let tsv = match fs::read_to_string(&file) {
+                Ok(tsv) => tsv,
+                Err(e) => {
+                    println!("Couldn't read {}: {}", file.display(), e);
+                    return MtgCardExit::NicknameError;
+                }
+            };
+            let nicknames = match parse_nickname_tsv(&tsv) {
+                Ok(nicknames) => nicknames,
+                Err(e) => {
+                    println!("{}", e);
+                    return MtgCardExit::NicknameError;
+                }
+            };
+            let mut added = 0;
+            for (nickname, card_name) in nicknames {
+                match add_nickname(&nickname, &card_name) {
+                    Ok(_) => added += 1,
+                    Err(e) => println!("Skipping \"{}\": {}", nickname, e),
+                }
+            }
+            println!("Added {} nickname(s)", added);
+            MtgCardExit::ChangedNicknames
+        }
+        NicknameCommand::Export { file } => {
+            if let Err(e) = fs::write(&file, nicknames_to_tsv(&get_all_nicknames())) {
+                println!("Couldn't write {}: {}", file.display(), e);
+                return MtgCardExit::NicknameError;
+            }
+            MtgCardExit::ListedNicknames
+        }
+    }
+}
+
 fn exact_search(search_strings: Vec<String>) -> MtgCardExit {
     let search_string = search_strings.join(" ");
     let card = get_card_by_name(&search_string);
@@ -71,6 +248,82 @@ fn exact_search(search_strings: Vec<String>) -> MtgCardExit {
     }
 }
 
+fn oracle_search(search_strings: Vec<String>) -> MtgCardExit {
+    let search_string = join_search_args(&search_strings);
+    let mut cards = find_cards_by_oracle_text(&search_string);
+    match cards.len() {
+        0 => {
+            println!("No cards found with oracle text matching {}", search_string);
+            MtgCardExit::NoExactMatchCard
+        }
+        1 => {
+            println!("{}", magic_finder::get_display_string(&cards.remove(0)));
+            MtgCardExit::ExactCardFound
+        }
+        _ => {
+            for card in cards {
+                println!("{}", card.name);
+            }
+            MtgCardExit::MultipleCardsMatch
+        }
+    }
+}
+
+fn prints_search(
+    search_strings: Vec<String>,
+    cheapest_only: bool,
+    spelling_distance: usize,
+) -> MtgCardExit {
+    match try_match_card_with_spelling_distance(
+        &join_search_args(&search_strings),
+        spelling_distance,
+    ) {
+        CardMatchResult::ExactCardFound(card) => {
+            println!("{}", card.name);
+            if cheapest_only {
+                for currency in Currency::ALL {
+                    if let Some((print, price)) = get_cheapest_print_for_card(&card, currency) {
+                        println!("{}\t{}", currency.format(price), print);
+                    }
+                }
+            } else {
+                for print in get_prints_for_card(&card) {
+                    println!("{}", print);
+                }
+            }
+            MtgCardExit::ExactCardFound
+        }
+        CardMatchResult::MultipleCardsMatch(cards) => {
+            for scored in cards {
+                println!("{}", scored.card.name);
+            }
+            MtgCardExit::MultipleCardsMatch
+        }
+        CardMatchResult::DidYouMean(magic_words, _) => {
+            for magic_word in magic_words {
+                println!("{}", magic_word);
+            }
+            MtgCardExit::DidYouMean
+        }
+        CardMatchResult::CloseCardNames(card_names) => {
+            for card_name in card_names {
+                println!("{}", card_name);
+            }
+            MtgCardExit::CloseCardNames
+        }
+        CardMatchResult::CorrectedQueries(queries) => {
+            for query in queries {
+                println!("{}", query);
+            }
+            MtgCardExit::CorrectedQueries
+        }
+        CardMatchResult::InvalidQuery(e) => {
+            println!("Couldn't understand the search: {}", e);
+            MtgCardExit::InvalidQuery
+        }
+    }
+}
+
 fn main() -> MtgCardExit {
     let args = Args::parse();
 
@@ -87,7 +340,7 @@ fn main() -> MtgCardExit {
         return MtgCardExit::PrintedDatabaseFolder;
     }
 
-    if args.search_text.is_empty() {
+    if args.search_text.is_empty() && args.command.is_none() {
         dbg!("You need to put some card text to search");
         return MtgCardExit::EmptySearchString;
     }
@@ -98,6 +351,10 @@ fn main() -> MtgCardExit {
                 println!("Database doesn't exist - did you update?");
                 return MtgCardExit::DbError;
             }
+            DbExistanceErrors::DbFileIsOutOfDate => {
+                println!("Database was made by an older version - run --update again");
+                return MtgCardExit::DbError;
+            }
             DbExistanceErrors::DbFileIsEmptyOfCards => {
                 println!("Database doesn't have any cards - try updating maybe?");
                 return MtgCardExit::DbError;
@@ -109,21 +366,85 @@ fn main() -> MtgCardExit {
         }
     }
 
+    match args.command {
+        Some(Command::Sets) => {
+            for set in get_all_sets() {
+                println!("{}", set);
+            }
+            return MtgCardExit::ListedSets;
+        }
+        Some(Command::Keywords) => {
+            for (keyword, card_count) in get_all_keywords() {
+                println!("{}\t{}", keyword, card_count);
+            }
+            return MtgCardExit::ListedKeywords;
+        }
+        Some(Command::Artist { name }) => {
+            let prints = get_prints_by_artist(&name.join(" "));
+            if prints.is_empty() {
+                println!("No cards found illustrated by {}", name.join(" "));
+                return MtgCardExit::NoExactMatchCard;
+            }
+            for (card_name, print) in prints {
+                println!("{}\t{}", card_name, print);
+            }
+            return MtgCardExit::ListedArtistPrints;
+        }
+        Some(Command::Complete { prefix, limit }) => {
+            for card_name in get_card_name_completions(&prefix.join(" "), limit) {
+                println!("{}", card_name);
+            }
+            return MtgCardExit::ListedCompletions;
+        }
+        Some(Command::Nickname { action }) => return nickname_command(action),
+        None => (),
+    }
+
     if args.exact {
         let res = exact_search(args.search_text);
         return res;
     }
 
-    match try_match_card(&args.search_text) {
+    if args.oracle {
+        return oracle_search(args.search_text);
+    }
+
+    if args.prints || args.cheapest {
+        return prints_search(args.search_text, args.cheapest, args.spelling_distance);
+    }
+
+    let mut search_text = args.search_text;
+    if let Some(order) = args.order {
+        search_text.push(format!("order:{}", order));
+    }
+    if let Some(direction) = args.direction {
+        search_text.push(format!("direction:{}", direction));
+    }
+    match try_match_card_with_spelling_distance(
+        &join_search_args(&search_text),
+        args.spelling_distance,
+    ) {
         CardMatchResult::DidYouMean(magic_words, _) => {
             for magic_word in magic_words {
                 println!("{}", magic_word);
             }
             MtgCardExit::DidYouMean
         }
+        CardMatchResult::CloseCardNames(card_names) => {
+            for card_name in card_names {
+                println!("{}", card_name);
+            }
+            MtgCardExit::CloseCardNames
+        }
+        CardMatchResult::CorrectedQueries(queries) => {
+            for query in queries {
+                println!("{}", query);
+            }
+            MtgCardExit::CorrectedQueries
+        }
         CardMatchResult::MultipleCardsMatch(cards) => {
-            for card in cards {
-                println!("{}", card.name);
+            for scored in cards {
+                println!("{}", scored.card.name);
             }
             MtgCardExit::MultipleCardsMatch
         }
@@ -131,6 +452,10 @@ fn main() -> MtgCardExit {
             println!("{}", magic_finder::get_display_string(&card));
             MtgCardExit::ExactCardFound
         }
+        CardMatchResult::InvalidQuery(e) => {
+            println!("Couldn't understand the search: {}", e);
+            MtgCardExit::InvalidQuery
+        }
     }
 }
 
@@ -145,3 +470,50 @@ fn _combine_search_strings(search_strings: Vec<String>) -> String {
     search_string.pop();
     search_string
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Args {
+        Args::try_parse_from(["magic_finder_cli"].iter().chain(args)).unwrap()
+    }
+
+    #[test]
+    fn test_searches_starting_with_command_names() {
+        // Complete Disregard, Sets of the Multiverse style names need a -- to be searches
+        for words in [
+            vec!["complete", "disregard"],
+            vec!["sets", "of", "the"],
+            vec!["artist", "proof"],
+            vec!["nickname"],
+            vec!["keywords"],
+        ] {
+            let args = parse(&[&["--"], words.as_slice()].concat());
+            assert_eq!(args.search_text, words);
+            assert!(args.command.is_none());
+        }
+        let args = parse(&["-o", "--", "sets", "of", "the"]);
+        assert!(args.oracle);
+        assert_eq!(args.search_text, vec!["sets", "of", "the"]);
+    }
+
+    #[test]
+    fn test_commands() {
+        let args = parse(&["complete", "--limit", "5", "lig", "bo"]);
+        assert!(
+            matches!(args.command, Some(Command::Complete { prefix, limit: 5 }) if prefix == ["lig", "bo"])
+        );
+        assert!(args.search_text.is_empty());
+
+        let args = parse(&["nickname", "add", "k command", "kolaghan's", "command"]);
+        assert!(matches!(
+            args.command,
+            Some(Command::Nickname {
+                action: NicknameCommand::Add { nickname, .. }
+            }) if nickname == "k command"
+        ));
+
+        assert!(matches!(parse(&["sets"]).command, Some(Command::Sets)));
+    }
+}
@@ -1,12 +1,12 @@
 use magic_finder::CardMatchResult;
 use magic_finder::DbCard;
 use magic_finder::DbExistanceErrors;
+use magic_finder::ScoredCard;
 use magic_finder::check_db_exists_and_populated;
 use magic_finder::get_card_by_name;
 use magic_finder::get_db_connection;
 use magic_finder::get_display_string;
 use magic_finder::init_db;
-use magic_finder::try_find_card_with_nickname;
 use magic_finder::try_match_card;
 use magic_finder::update_db_with_file;
 use std::env;
@@ -60,7 +60,8 @@ fn rofi_show_did_you_mean(magic_words: &[String]) -> String {
     output.to_string()
 }
 
-fn rofi_select_from_multiple_cards(cards: Vec<DbCard>) -> String {
+// Most likely card first, so it's the one highlighted
+fn rofi_select_from_multiple_cards(cards: Vec<ScoredCard>) -> String {
     let mut child = Command::new("rofi")
         .arg("-dmenu")
         .arg("-i")
@@ -71,12 +72,8 @@ fn rofi_select_from_multiple_cards(cards: Vec<DbCard>) -> String {
         .unwrap();
     let child_stdin = child.stdin.as_mut().unwrap();
     let mut card_name_strings = String::new();
-    for card in cards {
-        card_name_strings.push_str(&card.name);
-        match card.oc_name {
-            None => (),
-            Some(oc_name) => card_name_strings.push_str(&format!(" // {}", oc_name).to_string()),
-        }
+    for ScoredCard { card, .. } in cards {
+        card_name_strings.push_str(&card.full_name());
         card_name_strings.push('\n');
     }
     let _ = child_stdin.write_all(card_name_strings.as_bytes());
@@ -93,6 +90,50 @@ fn rofi_select_from_multiple_cards(cards: Vec<DbCard>) -> String {
     }
 }
 
+fn rofi_select_from_close_card_names(card_names: &[String]) {
+    let selected_card = rofi_show_did_you_mean(card_names);
+    if selected_card.is_empty() {
+        panic!("You probably exited early. You didn't select a card");
+    }
+    let selected_card = get_card_by_name(&selected_card).unwrap();
+    rofi_print_card(&selected_card);
+}
+
+// After picking a suggestion there's no more suggesting - it either finds something or it doesn't
+fn rofi_re_search(search_text: &str) {
+    let card_search_result = try_match_card(search_text);
+    dbg!(&card_search_result);
+    match card_search_result {
+        // This code is a bit of a double up of the codeblock in main
+        CardMatchResult::DidYouMean(_, _) | CardMatchResult::CorrectedQueries(_) => {
+            rofi_print_error(
+                &format!(
+                    "Couldn't find any cards with the strings \"{}\".",
+                    search_text
+                )
+                .to_string(),
+            );
+        }
+        CardMatchResult::MultipleCardsMatch(cards) => {
+            let selected_card = rofi_select_from_multiple_cards(cards);
+            if selected_card.is_empty() {
+                panic!("You probably exited early. You didn't select a card");
+            }
+            let selected_card = get_card_by_name(&selected_card).unwrap();
+            rofi_print_card(&selected_card);
+        }
+        CardMatchResult::CloseCardNames(card_names) => {
+            rofi_select_from_close_card_names(&card_names);
+        }
+        CardMatchResult::ExactCardFound(card) => {
+            rofi_print_card(&card);
+        }
+        CardMatchResult::InvalidQuery(e) => {
+            rofi_print_error(&format!("Couldn't understand the search: {}", e));
+        }
+    }
+}
+
 fn rofi_get_filename() -> String {
     let output = Command::new("rofi")
         .args(["-modi", "filebrowser"])
@@ -133,6 +174,9 @@ fn main() {
             DbExistanceErrors::DbFileDoesntExist => {
                 rofi_print_error("Database doesn't exist - did you run --update?");
             }
+            DbExistanceErrors::DbFileIsOutOfDate => {
+                rofi_print_error("Database was made by an older version - run --update again");
+            }
             DbExistanceErrors::DbFileIsEmptyOfCards => {
                 rofi_print_error("Database doesn't have any cards - try updating maybe?");
             }
@@ -152,18 +196,7 @@ fn main() {
         panic!("You need to put a search string in");
     }
 
-    let nickname_card = try_find_card_with_nickname(&search_text);
-    if let Some(name) = nickname_card {
-        let card = get_card_by_name(name).expect("This should always return a well known card");
-        rofi_print_card(&card);
-        return;
-    }
-
-    let mut search_text_words = Vec::new();
-    for word in search_text.split_whitespace() {
-        search_text_words.push(word.to_string());
-    }
-    let card_search_result = try_match_card(&search_text_words);
+    let card_search_result = try_match_card(&search_text);
     match card_search_result {
         CardMatchResult::DidYouMean(close_magic_words, exact_magic_words) => {
             if close_magic_words.is_empty() {
@@ -182,31 +215,17 @@ fn main() {
             for word in exact_magic_words {
                 re_search_words.push(word);
             }
-            let card_search_result = try_match_card(&re_search_words);
-            dbg!(&card_search_result);
-            match card_search_result {
-                // This code is a bit of a double up of next codebock
-                CardMatchResult::DidYouMean(_, _) => {
-                    rofi_print_error(
-                        &format!(
-                            "Couldn't find any cards with the strings \"{}\".",
-                            re_search_words.join(" ")
-                        )
-                        .to_string(),
-                    );
-                }
-                CardMatchResult::MultipleCardsMatch(cards) => {
-                    let selected_card = rofi_select_from_multiple_cards(cards);
-                    if selected_card.is_empty() {
-                        panic!("You probably exited early. You didn't select a card");
-                    }
-                    let selected_card = get_card_by_name(&selected_card).unwrap();
-                    rofi_print_card(&selected_card);
-                }
-                CardMatchResult::ExactCardFound(card) => {
-                    rofi_print_card(&card);
-                }
+            rofi_re_search(&re_search_words.join(" "));
+        }
+        CardMatchResult::CorrectedQueries(queries) => {
+            let corrected_query = rofi_show_did_you_mean(&queries);
+            if corrected_query.is_empty() {
+                panic!("You probably exited early. You didn't select a search");
             }
+            rofi_re_search(&corrected_query);
+        }
+        CardMatchResult::CloseCardNames(card_names) => {
+            rofi_select_from_close_card_names(&card_names);
         }
         CardMatchResult::MultipleCardsMatch(cards) => {
             let selected_card = rofi_select_from_multiple_cards(cards);
@@ -219,5 +238,8 @@ fn main() {
         CardMatchResult::ExactCardFound(card) => {
             rofi_print_card(&card);
         }
+        CardMatchResult::InvalidQuery(e) => {
+            rofi_print_error(&format!("Couldn't understand the search: {}", e));
+        }
     }
 }
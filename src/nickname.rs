@@ -0,0 +1,133 @@
+use super::normalise::normalise_search_text;
+use std::fmt;
+
+// The nicknames every database starts with. User added nicknames go in the same table - see
+//  add_nickname in db.rs
+pub const BUNDLED_NICKNAMES: &str = include_str!("nicknames.tsv");
+
+#[derive(Debug, PartialEq)]
+pub enum NicknameError {
+    CardNotFound(String),
+    // The line number (starting at 1) and the line
+    InvalidLine(usize, String),
+}
+
+impl fmt::Display for NicknameError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            NicknameError::CardNotFound(name) => write!(f, "There's no card called \"{}\"", name),
+            NicknameError::InvalidLine(number, line) => write!(
+                f,
+                "Line {} should be a nickname, a tab, then a card name: \"{}\"",
+                number, line
+            ),
+        }
+    }
+}
+
+// One "nickname<TAB>card name" per line. Blank lines and lines starting with # are skipped.
+pub fn parse_nickname_tsv(tsv: &str) -> Result<Vec<(String, String)>, NicknameError> {
+    let mut nicknames = Vec::new();
+    for (i, line) in tsv.lines().enumerate() {
+        if line.trim().is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let invalid = || NicknameError::InvalidLine(i + 1, line.to_string());
+        let (nickname, card_name) = line.split_once('\t').ok_or_else(invalid)?;
+        let nickname = normalise_search_text(nickname);
+        let card_name = card_name.trim();
+        if nickname.is_empty() || card_name.is_empty() || card_name.contains('\t') {
+            return Err(invalid());
+        }
+        nicknames.push((nickname, card_name.to_string()));
+    }
+    Ok(nicknames)
+}
+
+pub fn nicknames_to_tsv(nicknames: &[(String, String)]) -> String {
+    nicknames
+        .iter()
+        .map(|(nickname, card_name)| format!("{}\t{}\n", nickname, card_name))
+        .collect()
+}
+
+// Little words people might or might not include, like the t in jtms
+const SMALL_WORDS: [&str; 2] = ["of", "the"];
+
+// The initials people search for a card by, like "jvp" for Jace, Vryn's Prodigy. Both with and
+//  without "of" and "the", so Jace, the Mind Sculptor is "jtms" and "jms". Hyphens split words
+//  (see normalise_search_text), and one letter isn't enough to be worth it.
+pub fn name_initialisms(name: &str) -> Vec<String> {
+    let name = normalise_search_text(name);
+    let words: Vec<(char, bool)> = name
+        .split_whitespace()
+        .filter_map(|word| {
+            word.chars()
+                .next()
+                .map(|initial| (initial, SMALL_WORDS.contains(&word)))
+        })
+        .collect();
+    let all: String = words.iter().map(|(initial, _)| initial).collect();
+    let without_small: String = words
+        .iter()
+        .filter(|(_, small)| !small)
+        .map(|(initial, _)| initial)
+        .collect();
+    let mut initialisms = Vec::new();
+    for initialism in [all, without_small] {
+        if initialism.chars().count() >= 2 && !initialisms.contains(&initialism) {
+            initialisms.push(initialism);
+        }
+    }
+    initialisms
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_tsv() {
+        let tsv = "# A comment\nBob\tDark Confidant\n\n tron \tUrza's Mine\ntron\tUrza's Tower\n";
+        assert_eq!(
+            parse_nickname_tsv(tsv).unwrap(),
+            vec![
+                ("bob".to_string(), "Dark Confidant".to_string()),
+                ("tron".to_string(), "Urza's Mine".to_string()),
+                ("tron".to_string(), "Urza's Tower".to_string()),
+            ]
+        );
+        assert_eq!(
+            parse_nickname_tsv("bob\tDark Confidant\nbolt Lightning Bolt"),
+            Err(NicknameError::InvalidLine(
+                2,
+                "bolt Lightning Bolt".to_string()
+            ))
+        );
+        assert!(parse_nickname_tsv("\tDark Confidant").is_err());
+    }
+
+    #[test]
+    fn initialisms() {
+        assert_eq!(name_initialisms("Jace, Vryn's Prodigy"), vec!["jvp"]);
+        assert_eq!(
+            name_initialisms("Jace, the Mind Sculptor"),
+            vec!["jtms", "jms"]
+        );
+        assert_eq!(name_initialisms("Birds of Paradise"), vec!["bop", "bp"]);
+        assert_eq!(name_initialisms("Swords to Plowshares"), vec!["stp"]);
+        assert_eq!(name_initialisms("Lim-Dûl's Vault"), vec!["ldv"]);
+        assert_eq!(name_initialisms("Tarmogoyf"), Vec::<String>::new());
+        assert_eq!(name_initialisms("The Ur-Dragon"), vec!["tud", "ud"]);
+    }
+
+    #[test]
+    fn tsv_round_trip() {
+        let nicknames = parse_nickname_tsv(BUNDLED_NICKNAMES).unwrap();
+        assert!(nicknames.contains(&("k command".to_string(), "Kolaghan's Command".to_string())));
+        assert_eq!(
+            parse_nickname_tsv(&nicknames_to_tsv(&nicknames)).unwrap(),
+            nicknames
+        );
+    }
+}
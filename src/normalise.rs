@@ -0,0 +1,53 @@
+use deunicode::deunicode;
+
+// Card names and searches for them all go through this, so "Lim-Dûl's Vault", "lim-dul's vault"
+//  and "lim duls vault" are the same thing. Accents and ligatures become plain letters (Æther is
+//  aether), apostrophes and other punctuation go, and hyphens, dashes and slashes split words.
+pub fn normalise_search_text(text: &str) -> String {
+    let text = deunicode(text).to_lowercase();
+    let mut normalised = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '-' | '/' => normalised.push(' '),
+            c if c.is_alphanumeric() || c.is_whitespace() => normalised.push(c),
+            _ => (),
+        }
+    }
+    normalised
+        .split_whitespace()
+        .collect::<Vec<&str>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn accents_and_ligatures() {
+        assert_eq!(normalise_search_text("Lim-Dûl's Vault"), "lim duls vault");
+        assert_eq!(normalise_search_text("Jötun Grunt"), "jotun grunt");
+        assert_eq!(normalise_search_text("Æther Vial"), "aether vial");
+    }
+
+    #[test]
+    fn punctuation() {
+        assert_eq!(
+            normalise_search_text("Jace, Vryn’s Prodigy"),
+            "jace vryns prodigy"
+        );
+        assert_eq!(normalise_search_text("Fire // Ice"), "fire ice");
+        assert_eq!(
+            normalise_search_text("  \"Ach! Hans, Run!\" "),
+            "ach hans run"
+        );
+        assert_eq!(
+            normalise_search_text("Borrowing 100,000 Arrows"),
+            "borrowing 100000 arrows"
+        );
+        assert_eq!(
+            normalise_search_text("Kongming, “Sleeping Dragon”"),
+            "kongming sleeping dragon"
+        );
+    }
+}
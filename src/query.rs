@@ -0,0 +1,1584 @@
+use super::colour::{ColourSearch, Colours, colour_count_sql, parse_colour_search};
+use super::deser::{FORMAT_NAMES, Rarity};
+use super::mana::parse_mana_cost;
+use super::normalise::normalise_search_text;
+use super::type_line::TypeKind;
+use chrono::NaiveDate;
+use rusqlite::types::Value;
+use std::fmt;
+
+// A small subset of the Scryfall search syntax: https://scryfall.com/docs/syntax
+//  Bare words and "quoted phrases" search the card name, `key:value` (or =, !=, <, <=, >, >=)
+//  searches a particular field, `-` negates, `or` and parentheses group things, and everything
+//  else is ANDed together.
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Operator {
+    Colon,
+    Equal,
+    NotEqual,
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+}
+
+impl Operator {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Operator::Colon => ":",
+            Operator::Equal => "=",
+            Operator::NotEqual => "!=",
+            Operator::Less => "<",
+            Operator::LessOrEqual => "<=",
+            Operator::Greater => ">",
+            Operator::GreaterOrEqual => ">=",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Query {
+    And(Vec<Query>),
+    Or(Vec<Query>),
+    Not(Box<Query>),
+    Name(String),
+    ExactName(String),
+    Filter {
+        key: String,
+        operator: Operator,
+        value: String,
+    },
+}
+
+#[derive(Debug, PartialEq)]
+pub enum QueryError {
+    EmptyQuery,
+    UnclosedQuote,
+    UnmatchedParenthesis,
+    DanglingOperator(String),
+    UnknownKeyword(String),
+    UnsupportedOperator(String, Operator),
+    InvalidValue(String, String),
+    MisplacedOrder(String),
+}
+
+impl fmt::Display for QueryError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            QueryError::EmptyQuery => write!(f, "There's nothing to search for"),
+            QueryError::UnclosedQuote => write!(f, "There's a quote (\") that isn't closed"),
+            QueryError::UnmatchedParenthesis => write!(f, "The parentheses don't match up"),
+            QueryError::DanglingOperator(o) => {
+                write!(f, "\"{}\" needs something to search for after it", o)
+            }
+            QueryError::UnknownKeyword(k) => write!(f, "\"{}\" isn't a keyword I know", k),
+            QueryError::UnsupportedOperator(k, o) => {
+                write!(f, "\"{}\" can't be used with \"{}\"", o.as_str(), k)
+            }
+            QueryError::InvalidValue(k, v) => {
+                write!(f, "\"{}\" isn't something \"{}\" can search for", v, k)
+            }
+            QueryError::MisplacedOrder(k) => {
+                write!(f, "\"{}\" can't be negated or go inside an \"or\"", k)
+            }
+        }
+    }
+}
+
+fn quote_if_needed(text: &str) -> String {
+    if text.is_empty() || text.contains(char::is_whitespace) || text.contains(['(', ')']) {
+        format!("\"{}\"", text)
+    } else {
+        text.to_string()
+    }
+}
+
+// Writes the query back out in the same syntax it was parsed from
+impl fmt::Display for Query {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Query::And(queries) => {
+                let parts: Vec<String> = queries
+                    .iter()
+                    .map(|q| match q {
+                        Query::Or(_) => format!("({})", q),
+                        _ => q.to_string(),
+                    })
+                    .collect();
+                write!(f, "{}", parts.join(" "))
+            }
+            Query::Or(queries) => {
+                let parts: Vec<String> = queries
+                    .iter()
+                    .map(|q| match q {
+                        Query::And(_) => format!("({})", q),
+                        _ => q.to_string(),
+                    })
+                    .collect();
+                write!(f, "{}", parts.join(" or "))
+            }
+            Query::Not(query) => match query.as_ref() {
+                Query::And(_) | Query::Or(_) => write!(f, "-({})", query),
+                _ => write!(f, "-{}", query),
+            },
+            Query::Name(name) => write!(f, "{}", quote_if_needed(name)),
+            Query::ExactName(name) => write!(f, "!{}", quote_if_needed(name)),
+            Query::Filter {
+                key,
+                operator,
+                value,
+            } => write!(f, "{}{}{}", key, operator.as_str(), quote_if_needed(value)),
+        }
+    }
+}
+
+impl Query {
+    // Whether an order: or direction: was given, which beats ranking by relevance
+    pub fn has_ordering(&self) -> bool {
+        match self {
+            Query::And(queries) => queries.iter().any(is_ordering),
+            _ => is_ordering(self),
+        }
+    }
+
+    // All the plain words being searched for in card names, for things like spell checking
+    pub fn name_words(&self) -> Vec<String> {
+        match self {
+            Query::And(queries) | Query::Or(queries) => {
+                queries.iter().flat_map(|q| q.name_words()).collect()
+            }
+            Query::Name(name) => normalise_search_text(name)
+                .split_whitespace()
+                .map(|w| w.to_string())
+                .collect(),
+            Query::Not(_) | Query::ExactName(_) | Query::Filter { .. } => Vec::new(),
+        }
+    }
+
+    // The same query with its name words (in name_words order) swapped for others. Everything
+    //  else - or groups, quoted phrases, filters - stays where it was.
+    pub fn with_name_words(&self, words: &[String]) -> Query {
+        let mut next = 0;
+        self.replace_name_words(words, &mut next)
+    }
+
+    fn replace_name_words(&self, words: &[String], next: &mut usize) -> Query {
+        match self {
+            Query::And(queries) => Query::And(
+                queries
+                    .iter()
+                    .map(|q| q.replace_name_words(words, next))
+                    .collect(),
+            ),
+            Query::Or(queries) => Query::Or(
+                queries
+                    .iter()
+                    .map(|q| q.replace_name_words(words, next))
+                    .collect(),
+            ),
+            Query::Name(name) => {
+                let count = normalise_search_text(name).split_whitespace().count();
+                let end = (*next + count).min(words.len());
+                let name = words[*next..end].join(" ");
+                *next = end;
+                Query::Name(name)
+            }
+            Query::Not(_) | Query::ExactName(_) | Query::Filter { .. } => self.clone(),
+        }
+    }
+
+    // The query with all the plain name words taken out, so the filters can be kept when the
+    //  name words get swapped out for something else
+    pub fn without_names(&self) -> Option<Query> {
+        match self {
+            Query::Name(_) => None,
+            Query::And(queries) => {
+                let mut remaining: Vec<Query> =
+                    queries.iter().filter_map(|q| q.without_names()).collect();
+                match remaining.len() {
+                    0 => None,
+                    1 => remaining.pop(),
+                    _ => Some(Query::And(remaining)),
+                }
+            }
+            // Can't really take a name out of an "or" without changing what it means
+            Query::Or(_) => {
+                if self.name_words().is_empty() {
+                    Some(self.clone())
+                } else {
+                    None
+                }
+            }
+            Query::Not(_) | Query::ExactName(_) | Query::Filter { .. } => Some(self.clone()),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum Token {
+    LeftParen,
+    RightParen,
+    Minus,
+    Or,
+    Word(String),
+    Phrase(String),
+    ExactName(String),
+    Filter(String, Operator, String),
+}
+
+// Every key a filter can have. Anything else before an operator is just part of a name.
+const FILTER_KEYS: &[&str] = &[
+    "a",
+    "artist",
+    "banned",
+    "c",
+    "cardtype",
+    "ci",
+    "cmc",
+    "cn",
+    "color",
+    "colour",
+    "date",
+    "def",
+    "defense",
+    "direction",
+    "e",
+    "edition",
+    "eur",
+    "f",
+    "flavor",
+    "flavour",
+    "format",
+    "ft",
+    "id",
+    "identity",
+    "is",
+    "keyword",
+    "kw",
+    "legal",
+    "loy",
+    "loyalty",
+    "m",
+    "mana",
+    "manavalue",
+    "mv",
+    "n",
+    "name",
+    "number",
+    "o",
+    "oracle",
+    "order",
+    "pow",
+    "power",
+    "produces",
+    "r",
+    "rarity",
+    "restricted",
+    "s",
+    "set",
+    "settype",
+    "st",
+    "subtype",
+    "supertype",
+    "t",
+    "tix",
+    "tou",
+    "toughness",
+    "type",
+    "usd",
+    "watermark",
+    "wm",
+    "year",
+];
+
+fn is_word_end(c: char) -> bool {
+    c.is_whitespace() || c == ')'
+}
+
+fn read_quoted(chars: &[char], i: &mut usize) -> Result<String, QueryError> {
+    // Skip the opening quote
+    *i += 1;
+    let start = *i;
+    while *i < chars.len() && chars[*i] != '"' {
+        *i += 1;
+    }
+    if *i == chars.len() {
+        return Err(QueryError::UnclosedQuote);
+    }
+    let text: String = chars[start..*i].iter().collect();
+    // Skip the closing quote
+    *i += 1;
+    Ok(text)
+}
+
+fn read_until_word_end(chars: &[char], i: &mut usize) -> String {
+    let start = *i;
+    while *i < chars.len() && !is_word_end(chars[*i]) {
+        *i += 1;
+    }
+    chars[start..*i].iter().collect()
+}
+
+fn read_operator(chars: &[char], i: &mut usize) -> Option<Operator> {
+    let next = chars.get(*i + 1).copied();
+    let (operator, length) = match (chars.get(*i).copied()?, next) {
+        (':', _) => (Operator::Colon, 1),
+        ('=', _) => (Operator::Equal, 1),
+        ('!', Some('=')) => (Operator::NotEqual, 2),
+        ('<', Some('=')) => (Operator::LessOrEqual, 2),
+        ('<', _) => (Operator::Less, 1),
+        ('>', Some('=')) => (Operator::GreaterOrEqual, 2),
+        ('>', _) => (Operator::Greater, 1),
+        _ => return None,
+    };
+    *i += length;
+    Some(operator)
+}
+
+fn tokenise(text: &str) -> Result<Vec<Token>, QueryError> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        if c.is_whitespace() {
+            i += 1;
+            continue;
+        }
+        match c {
+            '(' => {
+                tokens.push(Token::LeftParen);
+                i += 1;
+            }
+            ')' => {
+                tokens.push(Token::RightParen);
+                i += 1;
+            }
+            '-' if chars.get(i + 1).is_some_and(|n| !n.is_whitespace()) => {
+                tokens.push(Token::Minus);
+                i += 1;
+            }
+            '"' => tokens.push(Token::Phrase(read_quoted(&chars, &mut i)?)),
+            '!' if chars.get(i + 1).is_some_and(|n| !is_word_end(*n)) => {
+                i += 1;
+                let name = if chars[i] == '"' {
+                    read_quoted(&chars, &mut i)?
+                } else {
+                    read_until_word_end(&chars, &mut i)
+                };
+                tokens.push(Token::ExactName(name));
+            }
+            _ => {
+                let start = i;
+                while i < chars.len() && chars[i].is_ascii_alphabetic() {
+                    i += 1;
+                }
+                let key: String = chars[start..i].iter().collect();
+                let operator = if FILTER_KEYS.contains(&key.to_lowercase().as_str()) {
+                    read_operator(&chars, &mut i)
+                } else {
+                    None
+                };
+                let value = match operator {
+                    Some(_) if chars.get(i) == Some(&'"') => read_quoted(&chars, &mut i)?,
+                    Some(_) => read_until_word_end(&chars, &mut i),
+                    None => String::new(),
+                };
+                match operator {
+                    Some(operator) if !value.is_empty() => {
+                        tokens.push(Token::Filter(key, operator, value));
+                    }
+                    // Not a filter after all, like "Circle of Protection: Red"
+                    _ => {
+                        i = start;
+                        let word = read_until_word_end(&chars, &mut i);
+                        match word.to_lowercase().as_str() {
+                            "or" => tokens.push(Token::Or),
+                            // "and" is what happens anyway
+                            "and" => (),
+                            _ => tokens.push(Token::Word(word)),
+                        }
+                    }
+                }
+            }
+        }
+    }
+    // Names with nothing left once they're normalised, like "!" or "-", would match every card
+    tokens.retain(|token| match token {
+        Token::Word(name) | Token::Phrase(name) | Token::ExactName(name) => {
+            !normalise_search_text(name).is_empty()
+        }
+        _ => true,
+    });
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    position: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get_mut(self.position)?;
+        // Swap something cheap in so the token can be moved out
+        let token = std::mem::replace(token, Token::Or);
+        self.position += 1;
+        Some(token)
+    }
+
+    fn parse_or(&mut self) -> Result<Query, QueryError> {
+        let mut branches = vec![self.parse_and()?];
+        while self.peek() == Some(&Token::Or) {
+            self.position += 1;
+            if self.peek().is_none() {
+                return Err(QueryError::DanglingOperator("or".to_string()));
+            }
+            branches.push(self.parse_and()?);
+        }
+        if branches.len() == 1 {
+            Ok(branches.pop().unwrap())
+        } else {
+            Ok(Query::Or(branches))
+        }
+    }
+
+    fn parse_and(&mut self) -> Result<Query, QueryError> {
+        let mut queries = Vec::new();
+        while let Some(token) = self.peek() {
+            if *token == Token::Or || *token == Token::RightParen {
+                break;
+            }
+            queries.push(self.parse_unary()?);
+        }
+        match queries.len() {
+            0 => match self.peek() {
+                Some(Token::Or) => Err(QueryError::DanglingOperator("or".to_string())),
+                Some(_) => Err(QueryError::UnmatchedParenthesis),
+                None => Err(QueryError::EmptyQuery),
+            },
+            1 => Ok(queries.pop().unwrap()),
+            _ => Ok(Query::And(queries)),
+        }
+    }
+
+    fn parse_unary(&mut self) -> Result<Query, QueryError> {
+        match self.next() {
+            Some(Token::Minus) => match self.peek() {
+                None | Some(Token::Or) | Some(Token::RightParen) => {
+                    Err(QueryError::DanglingOperator("-".to_string()))
+                }
+                _ => Ok(Query::Not(Box::new(self.parse_unary()?))),
+            },
+            Some(Token::LeftParen) => {
+                let query = self.parse_or()?;
+                match self.next() {
+                    Some(Token::RightParen) => Ok(query),
+                    _ => Err(QueryError::UnmatchedParenthesis),
+                }
+            }
+            Some(Token::Word(word)) | Some(Token::Phrase(word)) => Ok(Query::Name(word)),
+            Some(Token::ExactName(name)) => Ok(Query::ExactName(name)),
+            Some(Token::Filter(key, operator, value)) => Ok(Query::Filter {
+                key,
+                operator,
+                value,
+            }),
+            Some(Token::RightParen) => Err(QueryError::UnmatchedParenthesis),
+            Some(Token::Or) => Err(QueryError::DanglingOperator("or".to_string())),
+            None => Err(QueryError::EmptyQuery),
+        }
+    }
+}
+
+pub fn parse_query(text: &str) -> Result<Query, QueryError> {
+    let mut parser = Parser {
+        tokens: tokenise(text)?,
+        position: 0,
+    };
+    let query = parser.parse_or()?;
+    if parser.peek().is_some() {
+        return Err(QueryError::UnmatchedParenthesis);
+    }
+    Ok(query)
+}
+
+// The shell eats the quotes in something like `o:"draw a card"`, so put them back before the
+//  arguments get joined up into one query
+pub fn join_search_args(args: &[String]) -> String {
+    let mut joined = Vec::new();
+    for arg in args {
+        if !arg.contains(char::is_whitespace) || arg.contains('"') {
+            joined.push(arg.clone());
+            continue;
+        }
+        let key_length = arg
+            .trim_start_matches('-')
+            .chars()
+            .take_while(|c| c.is_ascii_alphabetic())
+            .count()
+            + (arg.len() - arg.trim_start_matches('-').len());
+        let chars: Vec<char> = arg.chars().collect();
+        let mut i = key_length;
+        match read_operator(&chars, &mut i) {
+            Some(_) if key_length > 0 => {
+                let (filter, value) = arg.split_at(i);
+                joined.push(format!("{}\"{}\"", filter, value));
+            }
+            _ => joined.push(format!("\"{}\"", arg)),
+        }
+    }
+    joined.join(" ")
+}
+
+fn fts_phrase(text: &str) -> Option<String> {
+    let prefix = text.ends_with('*');
+    let text = text.trim_end_matches('*').trim();
+    if !text.chars().any(|c| c.is_alphanumeric()) {
+        return None;
+    }
+    let prefix = if prefix { " *" } else { "" };
+    Some(format!("\"{}\"{}", text.replace('"', "\"\""), prefix))
+}
+
+// Turns search text into an FTS5 query for the oracle text. Every word has to be there, "quoted
+//  phrases" have to be there in that order, and a * on the end matches the start of a word
+//  (e.g. exil* gets exile, exiled, exiles...)
+pub fn fts_expression(text: &str) -> Option<String> {
+    let mut phrases = Vec::new();
+    for (i, chunk) in text.split('"').enumerate() {
+        if i % 2 == 1 {
+            phrases.extend(fts_phrase(chunk));
+        } else {
+            phrases.extend(chunk.split_whitespace().filter_map(fts_phrase));
+        }
+    }
+    if phrases.is_empty() {
+        None
+    } else {
+        Some(phrases.join(" "))
+    }
+}
+
+// The FTS tokeniser throws away symbols, so something like o:{T} or o:+1/+1 has to be done the
+//  slow way
+fn can_use_fts(text: &str) -> bool {
+    text.chars()
+        .all(|c| c.is_alphanumeric() || c.is_whitespace() || "'-,.*".contains(c))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SortOrder {
+    Name,
+    ManaValue,
+    Released,
+    Rarity,
+    Usd,
+    Edhrec,
+    Penny,
+    Power,
+    Toughness,
+    Set,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Direction {
+    Asc,
+    Desc,
+}
+
+impl SortOrder {
+    pub fn parse(order: &str) -> Option<SortOrder> {
+        match order.to_lowercase().as_str() {
+            "name" => Some(SortOrder::Name),
+            "mv" | "cmc" => Some(SortOrder::ManaValue),
+            "released" => Some(SortOrder::Released),
+            "rarity" => Some(SortOrder::Rarity),
+            "usd" => Some(SortOrder::Usd),
+            "edhrec" => Some(SortOrder::Edhrec),
+            "penny" => Some(SortOrder::Penny),
+            "power" | "pow" => Some(SortOrder::Power),
+            "toughness" | "tou" => Some(SortOrder::Toughness),
+            "set" => Some(SortOrder::Set),
+            _ => None,
+        }
+    }
+
+    // Newest and rarest first, smallest first for everything else (EDHREC and penny ranks are 1
+    //  for the most popular card)
+    fn default_direction(&self) -> Direction {
+        match self {
+            SortOrder::Released | SortOrder::Rarity => Direction::Desc,
+            _ => Direction::Asc,
+        }
+    }
+
+    fn sql(&self) -> &'static str {
+        match self {
+            SortOrder::Name => "name",
+            SortOrder::ManaValue => "mana_value",
+            SortOrder::Released => "released_at",
+            SortOrder::Rarity => {
+                "(SELECT MAX(CASE rarity WHEN 'common' THEN 0 WHEN 'uncommon' THEN 1 WHEN 'rare' THEN 2 WHEN 'special' THEN 3 WHEN 'mythic' THEN 4 ELSE 5 END) FROM prints WHERE prints.card_id = cards.id)"
+            }
+            // Cheapest finish of the cheapest print. The COALESCEs stop one missing price making
+            //  the whole thing NULL
+            SortOrder::Usd => {
+                "(SELECT MIN(MIN(COALESCE(usd, usd_foil, usd_etched), COALESCE(usd_foil, usd, usd_etched), COALESCE(usd_etched, usd, usd_foil))) FROM prints WHERE prints.card_id = cards.id)"
+            }
+            SortOrder::Edhrec => "edhrec_rank",
+            SortOrder::Penny => "penny_rank",
+            SortOrder::Power => {
+                "(SELECT power FROM card_stats WHERE card_stats.card_id = cards.id AND face = 0)"
+            }
+            SortOrder::Toughness => {
+                "(SELECT toughness FROM card_stats WHERE card_stats.card_id = cards.id AND face = 0)"
+            }
+            SortOrder::Set => {
+                "(SELECT set_code FROM prints WHERE prints.card_id = cards.id ORDER BY released_at LIMIT 1)"
+            }
+        }
+    }
+}
+
+impl Direction {
+    pub fn parse(direction: &str) -> Option<Direction> {
+        match direction.to_lowercase().as_str() {
+            "asc" => Some(Direction::Asc),
+            "desc" => Some(Direction::Desc),
+            _ => None,
+        }
+    }
+
+    fn sql(&self) -> &'static str {
+        match self {
+            Direction::Asc => "ASC",
+            Direction::Desc => "DESC",
+        }
+    }
+}
+
+// What a query turns into - a WHERE clause over the cards table (without the WHERE), an ORDER
+//  BY (without the ORDER BY) and the parameters to go along with them
+pub struct SqlFilter {
+    pub sql: String,
+    pub order_by: String,
+    pub params: Vec<Value>,
+}
+
+fn is_ordering(query: &Query) -> bool {
+    matches!(query, Query::Filter { key, .. } if matches!(key.to_lowercase().as_str(), "order" | "direction"))
+}
+
+// order: and direction: aren't really searches, so they can only be at the top level. Returns
+//  what's left of the query once they're taken out.
+fn take_ordering(
+    query: &Query,
+    order: &mut Option<SortOrder>,
+    direction: &mut Option<Direction>,
+) -> Result<Option<Query>, QueryError> {
+    let (orderings, rest): (Vec<&Query>, Vec<&Query>) = match query {
+        Query::And(queries) => queries.iter().partition(|q| is_ordering(q)),
+        _ if is_ordering(query) => (vec![query], Vec::new()),
+        _ => (Vec::new(), vec![query]),
+    };
+    for ordering in orderings {
+        let Query::Filter {
+            key,
+            operator,
+            value,
+        } = ordering
+        else {
+            continue;
+        };
+        if *operator != Operator::Colon && *operator != Operator::Equal {
+            return Err(QueryError::UnsupportedOperator(key.to_string(), *operator));
+        }
+        let invalid = || QueryError::InvalidValue(key.to_string(), value.to_string());
+        if key.eq_ignore_ascii_case("order") {
+            *order = Some(SortOrder::parse(value).ok_or_else(invalid)?);
+        } else {
+            *direction = Some(Direction::parse(value).ok_or_else(invalid)?);
+        }
+    }
+    Ok(match rest.len() {
+        0 => None,
+        1 => Some(rest[0].clone()),
+        _ => Some(Query::And(rest.into_iter().cloned().collect())),
+    })
+}
+
+// A LIKE pattern (with ESCAPE '\') matching anything containing the text, with % and _ taken
+//  literally
+pub fn like_pattern(text: &str) -> String {
+    let escaped = text
+        .to_lowercase()
+        .replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_");
+    format!("%{}%", escaped)
+}
+
+fn sql_operator(operator: Operator) -> &'static str {
+    match operator {
+        Operator::Colon | Operator::Equal => "=",
+        Operator::NotEqual => "!=",
+        Operator::Less => "<",
+        Operator::LessOrEqual => "<=",
+        Operator::Greater => ">",
+        Operator::GreaterOrEqual => ">=",
+    }
+}
+
+const PERMANENT_TYPES: &str =
+    "'Artifact', 'Battle', 'Creature', 'Enchantment', 'Land', 'Planeswalker'";
+
+// is: searches that are about a printing rather than the card
+fn print_is_condition(value: &str) -> Option<&'static str> {
+    match value {
+        "digital" => Some("digital"),
+        "promo" => Some("promo"),
+        "fullart" => Some("full_art"),
+        "textless" => Some("textless"),
+        "funny" => Some("set_code IN (SELECT code FROM sets WHERE set_type = 'funny')"),
+        _ => None,
+    }
+}
+
+// ANDed print searches all have to match the same printing, so s:dom cn:1 finds one card
+fn prints_matching(conditions: &[String]) -> String {
+    format!(
+        "id IN (SELECT card_id FROM prints WHERE {})",
+        conditions.join(" AND ")
+    )
+}
+
+// Searches that have to match on one of the card's faces - see card_faces
+fn faces_matching(condition: &str) -> String {
+    format!("id IN (SELECT card_id FROM card_faces WHERE {})", condition)
+}
+
+// Column in card_stats for pow:, tou: and friends
+fn stat_column(key: &str) -> Option<&'static str> {
+    match key.to_lowercase().as_str() {
+        "pow" | "power" => Some("power"),
+        "tou" | "toughness" => Some("toughness"),
+        "loy" | "loyalty" => Some("loyalty"),
+        "def" | "defense" => Some("defense"),
+        _ => None,
+    }
+}
+
+// The bits are worked out by us, not typed in, so they're fine to put straight in the SQL
+fn compare_colours(column: &str, operator: Operator, bits: u8) -> String {
+    let superset = format!("({} & {}) = {}", column, bits, bits);
+    let subset = format!("({} | {}) = {}", column, bits, bits);
+    match operator {
+        Operator::Colon | Operator::Equal => format!("{} = {}", column, bits),
+        Operator::NotEqual => format!("{} != {}", column, bits),
+        Operator::GreaterOrEqual => superset,
+        Operator::Greater => format!("{} AND {} != {}", superset, column, bits),
+        Operator::LessOrEqual => subset,
+        Operator::Less => format!("{} AND {} != {}", subset, column, bits),
+    }
+}
+
+struct Compiler {
+    params: Vec<Value>,
+}
+
+impl Compiler {
+    // Returns the placeholder to put in the SQL for the new parameter
+    fn push_param(&mut self, value: impl Into<Value>) -> String {
+        self.params.push(value.into());
+        format!("?{}", self.params.len())
+    }
+
+    fn compile(&mut self, query: &Query) -> Result<String, QueryError> {
+        match query {
+            Query::And(queries) => {
+                let mut parts = Vec::new();
+                let mut print_conditions = Vec::new();
+                for query in queries {
+                    if let Query::Filter {
+                        key,
+                        operator,
+                        value,
+                    } = query
+                        && let Some(condition) = self.print_condition(key, *operator, value)?
+                    {
+                        print_conditions.push(condition);
+                        continue;
+                    }
+                    parts.push(format!("({})", self.compile(query)?));
+                }
+                if !print_conditions.is_empty() {
+                    parts.push(format!("({})", prints_matching(&print_conditions)));
+                }
+                Ok(parts.join(" AND "))
+            }
+            Query::Or(queries) => self.compile_all(queries, " OR "),
+            // Lots of cards don't have things like an EDHREC rank, so they're NULL. NOT NULL is
+            //  still NULL, which would throw away those cards without the IFNULL
+            Query::Not(query) => Ok(format!("NOT IFNULL(({}), 0)", self.compile(query)?)),
+            Query::Name(name) => Ok(self.card_name(name, false)),
+            Query::ExactName(name) => Ok(self.card_name(name, true)),
+            Query::Filter {
+                key,
+                operator,
+                value,
+            } => self.compile_filter(key, *operator, value),
+        }
+    }
+
+    fn compile_all(&mut self, queries: &[Query], joiner: &str) -> Result<String, QueryError> {
+        let mut parts = Vec::new();
+        for query in queries {
+            parts.push(format!("({})", self.compile(query)?));
+        }
+        Ok(parts.join(joiner))
+    }
+
+    fn compile_filter(
+        &mut self,
+        key: &str,
+        operator: Operator,
+        value: &str,
+    ) -> Result<String, QueryError> {
+        if let Some(condition) = self.print_condition(key, operator, value)? {
+            return Ok(prints_matching(&[condition]));
+        }
+        let columns: &[&str] = match key.to_lowercase().as_str() {
+            "n" | "name" => {
+                return match operator {
+                    Operator::Colon => Ok(self.card_name(value, false)),
+                    Operator::Equal => Ok(self.card_name(value, true)),
+                    _ => Err(QueryError::UnsupportedOperator(key.to_string(), operator)),
+                };
+            }
+            "t" | "type" => return self.card_type(key, operator, value, None),
+            "supertype" => return self.card_type(key, operator, value, Some(TypeKind::Supertype)),
+            "cardtype" => return self.card_type(key, operator, value, Some(TypeKind::CardType)),
+            "subtype" => return self.card_type(key, operator, value, Some(TypeKind::Subtype)),
+            "o" | "oracle" => {
+                if operator == Operator::Colon
+                    && can_use_fts(value)
+                    && let Some(phrase) = fts_phrase(value)
+                {
+                    let param = self.push_param(phrase);
+                    return Ok(format!(
+                        "id IN (SELECT rowid FROM oracle_fts WHERE oracle_fts MATCH {})",
+                        param
+                    ));
+                }
+                &["oracle_text"]
+            }
+            "m" | "mana" => return self.mana_cost(key, operator, value),
+            "mv" | "cmc" | "manavalue" => return self.mana_value(key, operator, value),
+            "is" => return self.is(key, operator, value),
+            "order" | "direction" => return Err(QueryError::MisplacedOrder(key.to_string())),
+            "kw" | "keyword" => return self.keyword(key, operator, value),
+            // Restricted cards are still legal to play
+            "f" | "format" | "legal" => {
+                return self.legality(key, operator, value, &["legal", "restricted"]);
+            }
+            "banned" => return self.legality(key, operator, value, &["banned"]),
+            "restricted" => return self.legality(key, operator, value, &["restricted"]),
+            "pow" | "power" | "tou" | "toughness" | "loy" | "loyalty" | "def" | "defense" => {
+                return self.stat(key, operator, value);
+            }
+            // Like Scryfall, c: is "has at least these colours" and id: is "fits in this identity"
+            "c" | "color" | "colour" => {
+                return self.colours(key, operator, value, &["colours"], Operator::GreaterOrEqual);
+            }
+            "id" | "identity" | "ci" => {
+                return self.colours(
+                    key,
+                    operator,
+                    value,
+                    &["colour_identity"],
+                    Operator::LessOrEqual,
+                );
+            }
+            "produces" => {
+                return self.colours(
+                    key,
+                    operator,
+                    value,
+                    &["produced_mana"],
+                    Operator::GreaterOrEqual,
+                );
+            }
+            _ => return Err(QueryError::UnknownKeyword(key.to_string())),
+        };
+        // What's left is text that's on each face
+        let condition = match operator {
+            Operator::Colon => self.text_contains(columns, value),
+            Operator::Equal => self.text_equals(columns, value),
+            _ => return Err(QueryError::UnsupportedOperator(key.to_string(), operator)),
+        };
+        Ok(faces_matching(&condition))
+    }
+
+    // Types have to match whole words - so t:plane doesn't find planeswalkers. Something like
+    //  t:"legendary elf" needs all the types on the same face.
+    fn card_type(
+        &mut self,
+        key: &str,
+        operator: Operator,
+        value: &str,
+        kind: Option<TypeKind>,
+    ) -> Result<String, QueryError> {
+        if operator != Operator::Colon && operator != Operator::Equal {
+            return Err(QueryError::UnsupportedOperator(key.to_string(), operator));
+        }
+        let kind_filter = match kind {
+            Some(kind) => format!(" AND kind = {}", self.push_param(kind.as_str().to_string())),
+            None => String::new(),
+        };
+        let whole = self.push_param(value.trim().to_string());
+        let mut sql = format!(
+            "id IN (SELECT card_id FROM card_types WHERE type = {}{})",
+            whole, kind_filter
+        );
+        let words: Vec<&str> = value.split_whitespace().collect();
+        if words.len() > 1 {
+            let mut having = Vec::new();
+            for word in words {
+                having.push(format!(
+                    "SUM(type = {}) > 0",
+                    self.push_param(word.to_string())
+                ));
+            }
+            sql.push_str(&format!(
+                " OR id IN (SELECT card_id FROM card_types WHERE 1{} GROUP BY card_id, face HAVING {})",
+                kind_filter,
+                having.join(" AND ")
+            ));
+        }
+        Ok(sql)
+    }
+
+    fn colours(
+        &mut self,
+        key: &str,
+        operator: Operator,
+        value: &str,
+        columns: &[&str],
+        colon_means: Operator,
+    ) -> Result<String, QueryError> {
+        let search = match (key.to_lowercase().as_str(), parse_colour_search(value)) {
+            // Colourless means something different here - it's the {C} mana symbol
+            ("produces", Some(ColourSearch::Colourless)) => {
+                ColourSearch::Colours(Colours::colourless_mana())
+            }
+            (_, Some(search)) => search,
+            (_, None) => {
+                return Err(QueryError::InvalidValue(key.to_string(), value.to_string()));
+            }
+        };
+        // Colourless and multicolour are numbers of colours, so c!=c and c<m work too
+        let colon_means = match search {
+            ColourSearch::Colourless => Operator::Equal,
+            ColourSearch::Multicolour => Operator::GreaterOrEqual,
+            _ => colon_means,
+        };
+        let operator = match operator {
+            Operator::Colon => colon_means,
+            _ => operator,
+        };
+        let compare_count = |column: &str, count: u8| {
+            format!(
+                "({}) {} {}",
+                colour_count_sql(column),
+                sql_operator(operator),
+                count
+            )
+        };
+        let mut parts = Vec::new();
+        for column in columns {
+            let part = match search {
+                ColourSearch::Colours(colours) => compare_colours(column, operator, colours.bits()),
+                ColourSearch::Colourless => compare_count(column, 0),
+                ColourSearch::Multicolour => compare_count(column, 2),
+                ColourSearch::Count(count) => compare_count(column, count),
+            };
+            parts.push(part);
+        }
+        Ok(parts.join(" OR "))
+    }
+
+    // m:{R}{R} is any face with at least two {R}s in its cost, m={R}{R} is a face costing exactly
+    //  {R}{R}
+    fn mana_cost(
+        &mut self,
+        key: &str,
+        operator: Operator,
+        value: &str,
+    ) -> Result<String, QueryError> {
+        let mut counts: Vec<(String, usize)> = Vec::new();
+        for symbol in parse_mana_cost(value) {
+            let symbol = symbol.to_string();
+            match counts.iter_mut().find(|(s, _)| *s == symbol) {
+                Some((_, count)) => *count += 1,
+                None => counts.push((symbol, 1)),
+            }
+        }
+        if counts.is_empty() {
+            return Err(QueryError::InvalidValue(key.to_string(), value.to_string()));
+        }
+        let (comparison, exact) = match operator {
+            Operator::Colon | Operator::GreaterOrEqual => (">=", false),
+            Operator::Equal | Operator::NotEqual => ("=", true),
+            _ => return Err(QueryError::UnsupportedOperator(key.to_string(), operator)),
+        };
+        let mut having = Vec::new();
+        if exact {
+            let total: usize = counts.iter().map(|(_, count)| count).sum();
+            having.push(format!("COUNT(*) = {}", total));
+        }
+        for (symbol, count) in counts {
+            let param = self.push_param(symbol);
+            having.push(format!("SUM(symbol = {}) {} {}", param, comparison, count));
+        }
+        let sql = format!(
+            "id IN (SELECT card_id FROM mana_symbols GROUP BY card_id, face HAVING {})",
+            having.join(" AND ")
+        );
+        if operator == Operator::NotEqual {
+            Ok(format!("NOT ({})", sql))
+        } else {
+            Ok(sql)
+        }
+    }
+
+    fn mana_value(
+        &mut self,
+        key: &str,
+        operator: Operator,
+        value: &str,
+    ) -> Result<String, QueryError> {
+        let parity = match value.to_lowercase().as_str() {
+            "even" => Some(0),
+            "odd" => Some(1),
+            _ => None,
+        };
+        if let Some(parity) = parity {
+            let sql = format!(
+                "mana_value = CAST(mana_value AS INTEGER) AND CAST(mana_value AS INTEGER) % 2 = {}",
+                parity
+            );
+            return match operator {
+                Operator::Colon | Operator::Equal => Ok(sql),
+                Operator::NotEqual => Ok(format!("NOT ({})", sql)),
+                _ => Err(QueryError::UnsupportedOperator(key.to_string(), operator)),
+            };
+        }
+        let number: f64 = value
+            .parse()
+            .map_err(|_| QueryError::InvalidValue(key.to_string(), value.to_string()))?;
+        let param = self.push_param(number);
+        Ok(format!("mana_value {} {}", sql_operator(operator), param))
+    }
+
+    // The value can be a number (pow>=4) or another stat on the same face (tou>pow)
+    fn stat(&mut self, key: &str, operator: Operator, value: &str) -> Result<String, QueryError> {
+        let column = stat_column(key).unwrap();
+        let other = match stat_column(value) {
+            Some(other_column) => other_column.to_string(),
+            None => {
+                let number: f64 = value
+                    .parse()
+                    .map_err(|_| QueryError::InvalidValue(key.to_string(), value.to_string()))?;
+                self.push_param(number)
+            }
+        };
+        Ok(format!(
+            "id IN (SELECT card_id FROM card_stats WHERE {} {} {})",
+            column,
+            sql_operator(operator),
+            other
+        ))
+    }
+
+    fn legality(
+        &mut self,
+        key: &str,
+        operator: Operator,
+        value: &str,
+        legalities: &[&str],
+    ) -> Result<String, QueryError> {
+        if operator != Operator::Colon && operator != Operator::Equal {
+            return Err(QueryError::UnsupportedOperator(key.to_string(), operator));
+        }
+        let format = value.to_lowercase();
+        if !FORMAT_NAMES.contains(&format.as_str()) {
+            return Err(QueryError::InvalidValue(key.to_string(), value.to_string()));
+        }
+        let format = self.push_param(format);
+        // Legalities are our own names, not typed in, so they can go straight in the SQL
+        let legalities = legalities
+            .iter()
+            .map(|l| format!("'{}'", l))
+            .collect::<Vec<String>>()
+            .join(", ");
+        Ok(format!(
+            "id IN (SELECT card_id FROM legalities WHERE format = {} AND legality IN ({}))",
+            format, legalities
+        ))
+    }
+
+    // Returns None if the key isn't about a printing. The condition is on a single row of prints
+    fn print_condition(
+        &mut self,
+        key: &str,
+        operator: Operator,
+        value: &str,
+    ) -> Result<Option<String>, QueryError> {
+        let condition = match key.to_lowercase().as_str() {
+            "s" | "e" | "set" | "edition" => self.set(key, operator, value)?,
+            "st" | "settype" => self.set_type(key, operator, value)?,
+            "year" => self.year(key, operator, value)?,
+            "date" => self.date(key, operator, value)?,
+            "r" | "rarity" => self.rarity(key, operator, value)?,
+            "cn" | "number" => self.collector_number(key, operator, value)?,
+            "usd" => self.price(key, operator, value, &["usd", "usd_foil", "usd_etched"])?,
+            "eur" => self.price(key, operator, value, &["eur", "eur_foil"])?,
+            "tix" => self.price(key, operator, value, &["tix"])?,
+            "a" | "artist" => self.print_text(key, operator, value, "artist")?,
+            "ft" | "flavor" | "flavour" => self.print_text(key, operator, value, "flavour_text")?,
+            "wm" | "watermark" => self.print_text(key, operator, value, "watermark")?,
+            "is" => match print_is_condition(&value.to_lowercase()) {
+                Some(condition) if operator == Operator::Colon || operator == Operator::Equal => {
+                    condition.to_string()
+                }
+                Some(_) => return Err(QueryError::UnsupportedOperator(key.to_string(), operator)),
+                None => return Ok(None),
+            },
+            _ => return Ok(None),
+        };
+        Ok(Some(condition))
+    }
+
+    fn set(&mut self, key: &str, operator: Operator, value: &str) -> Result<String, QueryError> {
+        if operator != Operator::Colon && operator != Operator::Equal {
+            return Err(QueryError::UnsupportedOperator(key.to_string(), operator));
+        }
+        let code = self.push_param(value.to_lowercase());
+        Ok(format!("set_code = {}", code))
+    }
+
+    fn set_type(
+        &mut self,
+        key: &str,
+        operator: Operator,
+        value: &str,
+    ) -> Result<String, QueryError> {
+        if operator != Operator::Colon && operator != Operator::Equal {
+            return Err(QueryError::UnsupportedOperator(key.to_string(), operator));
+        }
+        let set_type = self.push_param(value.to_lowercase());
+        Ok(format!(
+            "set_code IN (SELECT code FROM sets WHERE set_type = {})",
+            set_type
+        ))
+    }
+
+    fn year(&mut self, key: &str, operator: Operator, value: &str) -> Result<String, QueryError> {
+        let year: i64 = value
+            .parse()
+            .map_err(|_| QueryError::InvalidValue(key.to_string(), value.to_string()))?;
+        let year = self.push_param(year);
+        Ok(format!(
+            "CAST(substr(released_at, 1, 4) AS INTEGER) {} {}",
+            sql_operator(operator),
+            year
+        ))
+    }
+
+    // Dates are stored as YYYY-MM-DD, so they compare fine as text
+    fn date(&mut self, key: &str, operator: Operator, value: &str) -> Result<String, QueryError> {
+        let date = NaiveDate::parse_from_str(value, "%Y-%m-%d")
+            .map_err(|_| QueryError::InvalidValue(key.to_string(), value.to_string()))?;
+        let date = self.push_param(date.to_string());
+        Ok(format!("released_at {} {}", sql_operator(operator), date))
+    }
+
+    // r>=rare is worked out here rather than in SQL, since the rarities are stored as names
+    fn rarity(&mut self, key: &str, operator: Operator, value: &str) -> Result<String, QueryError> {
+        let rarity = Rarity::parse(value)
+            .ok_or_else(|| QueryError::InvalidValue(key.to_string(), value.to_string()))?;
+        let matching: Vec<String> = Rarity::ALL
+            .iter()
+            .filter(|r| match operator {
+                Operator::Colon | Operator::Equal => **r == rarity,
+                Operator::NotEqual => **r != rarity,
+                Operator::Less => **r < rarity,
+                Operator::LessOrEqual => **r <= rarity,
+                Operator::Greater => **r > rarity,
+                Operator::GreaterOrEqual => **r >= rarity,
+            })
+            .map(|r| format!("'{}'", r.as_str()))
+            .collect();
+        if matching.is_empty() {
+            return Ok("0".to_string());
+        }
+        Ok(format!("rarity IN ({})", matching.join(", ")))
+    }
+
+    fn print_text(
+        &mut self,
+        key: &str,
+        operator: Operator,
+        value: &str,
+        column: &str,
+    ) -> Result<String, QueryError> {
+        match operator {
+            Operator::Colon => Ok(format!("({})", self.text_contains(&[column], value))),
+            Operator::Equal => Ok(format!("({})", self.text_equals(&[column], value))),
+            _ => Err(QueryError::UnsupportedOperator(key.to_string(), operator)),
+        }
+    }
+
+    // Matches if any finish (normal, foil, etched) of the print has a matching price
+    fn price(
+        &mut self,
+        key: &str,
+        operator: Operator,
+        value: &str,
+        columns: &[&str],
+    ) -> Result<String, QueryError> {
+        let price: f64 = value
+            .parse()
+            .map_err(|_| QueryError::InvalidValue(key.to_string(), value.to_string()))?;
+        let price = self.push_param(price);
+        let parts: Vec<String> = columns
+            .iter()
+            .map(|c| format!("{} {} {}", c, sql_operator(operator), price))
+            .collect();
+        Ok(format!("({})", parts.join(" OR ")))
+    }
+
+    // Collector numbers aren't always numbers (like 123a or ★), so only < and friends treat
+    //  them as one
+    fn collector_number(
+        &mut self,
+        key: &str,
+        operator: Operator,
+        value: &str,
+    ) -> Result<String, QueryError> {
+        match operator {
+            Operator::Colon | Operator::Equal | Operator::NotEqual => {
+                let number = self.push_param(value.to_lowercase());
+                Ok(format!(
+                    "LOWER(collector_number) {} {}",
+                    sql_operator(operator),
+                    number
+                ))
+            }
+            _ => {
+                let number: i64 = value
+                    .parse()
+                    .map_err(|_| QueryError::InvalidValue(key.to_string(), value.to_string()))?;
+                let number = self.push_param(number);
+                Ok(format!(
+                    "CAST(collector_number AS INTEGER) {} {}",
+                    sql_operator(operator),
+                    number
+                ))
+            }
+        }
+    }
+
+    fn keyword(
+        &mut self,
+        key: &str,
+        operator: Operator,
+        value: &str,
+    ) -> Result<String, QueryError> {
+        if operator != Operator::Colon && operator != Operator::Equal {
+            return Err(QueryError::UnsupportedOperator(key.to_string(), operator));
+        }
+        let keyword = self.push_param(value.to_string());
+        Ok(format!(
+            "id IN (SELECT card_id FROM card_keywords JOIN keywords ON keywords.id = card_keywords.keyword_id WHERE keywords.name = {})",
+            keyword
+        ))
+    }
+
+    fn is(&mut self, key: &str, operator: Operator, value: &str) -> Result<String, QueryError> {
+        if operator != Operator::Colon && operator != Operator::Equal {
+            return Err(QueryError::UnsupportedOperator(key.to_string(), operator));
+        }
+        match value.to_lowercase().as_str() {
+            "hybrid" => Ok("id IN (SELECT card_id FROM mana_symbols WHERE hybrid)".to_string()),
+            "phyrexian" => {
+                Ok("id IN (SELECT card_id FROM mana_symbols WHERE phyrexian)".to_string())
+            }
+            "reserved" => Ok("reserved".to_string()),
+            "gamechanger" => Ok("game_changer".to_string()),
+            "dfc" => Ok("layout IN ('transform', 'modal_dfc', 'reversible_card')".to_string()),
+            "split" => Ok("layout = 'split'".to_string()),
+            "adventure" => Ok("layout = 'adventure'".to_string()),
+            // Legendary creatures, or anything that says it can be your commander
+            "commander" => Ok(
+                "id IN (SELECT card_id FROM card_types WHERE face = 0 GROUP BY card_id HAVING SUM(kind = 'supertype' AND type = 'Legendary') AND SUM(kind = 'type' AND type = 'Creature')) OR id IN (SELECT card_id FROM card_faces WHERE face = 0 AND LOWER(oracle_text) LIKE '%can be your commander%')"
+                    .to_string(),
+            ),
+            "permanent" => Ok(format!(
+                "id IN (SELECT card_id FROM card_types WHERE face = 0 AND kind = 'type' AND type IN ({}))",
+                PERMANENT_TYPES
+            )),
+            // Anything that isn't a land, including artifact lands and Dryad Arbor
+            "spell" => Ok(
+                "id NOT IN (SELECT card_id FROM card_types WHERE face = 0 AND kind = 'type' AND type = 'Land')"
+                    .to_string(),
+            ),
+            _ => Err(QueryError::InvalidValue(key.to_string(), value.to_string())),
+        }
+    }
+
+    // Any face's name, or any name the card has been printed with (see card_aliases). Compared
+    //  normalised, so accents and punctuation don't matter.
+    fn card_name(&mut self, name: &str, exact: bool) -> String {
+        let name = normalise_search_text(name);
+        let (names, aliases) = if exact {
+            (
+                faces_matching(&self.text_equals(&["search_name"], &name)),
+                self.text_equals(&["search_alias"], &name),
+            )
+        } else {
+            (
+                faces_matching(&self.text_contains(&["search_name"], &name)),
+                self.text_contains(&["search_alias"], &name),
+            )
+        };
+        format!(
+            "{} OR id IN (SELECT card_id FROM card_aliases WHERE {})",
+            names, aliases
+        )
+    }
+
+    // Checks any of the columns contains the text
+    fn text_contains(&mut self, columns: &[&str], text: &str) -> String {
+        let param = self.push_param(like_pattern(text));
+        let parts: Vec<String> = columns
+            .iter()
+            .map(|c| format!("LOWER({}) LIKE {} ESCAPE '\\'", c, param))
+            .collect();
+        parts.join(" OR ")
+    }
+
+    fn text_equals(&mut self, columns: &[&str], text: &str) -> String {
+        let param = self.push_param(text.to_lowercase());
+        let parts: Vec<String> = columns
+            .iter()
+            .map(|c| format!("LOWER({}) = {}", c, param))
+            .collect();
+        parts.join(" OR ")
+    }
+}
+
+pub fn compile_query(query: &Query) -> Result<SqlFilter, QueryError> {
+    let mut order = None;
+    let mut direction = None;
+    let query = take_ordering(query, &mut order, &mut direction)?;
+    let mut compiler = Compiler { params: Vec::new() };
+    let sql = match query {
+        Some(query) => compiler.compile(&query)?,
+        None => "1".to_string(),
+    };
+    let order = order.unwrap_or(SortOrder::Name);
+    let direction = direction.unwrap_or(order.default_direction());
+    // Cards without the thing being sorted on (like an EDHREC rank) go at the end either way
+    let order_by = format!("{} {} NULLS LAST, name ASC", order.sql(), direction.sql());
+    Ok(SqlFilter {
+        sql,
+        order_by,
+        params: compiler.params,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn filter(key: &str, operator: Operator, value: &str) -> Query {
+        Query::Filter {
+            key: key.to_string(),
+            operator,
+            value: value.to_string(),
+        }
+    }
+
+    #[test]
+    fn parse_bare_words() {
+        assert_eq!(
+            parse_query("black lotus").unwrap(),
+            Query::And(vec![
+                Query::Name("black".to_string()),
+                Query::Name("lotus".to_string())
+            ])
+        );
+        assert_eq!(
+            parse_query("\"black lotus\"").unwrap(),
+            Query::Name("black lotus".to_string())
+        );
+        assert_eq!(
+            parse_query("!\"Black Lotus\"").unwrap(),
+            Query::ExactName("Black Lotus".to_string())
+        );
+        // Only known keys followed by something make a filter
+        assert_eq!(
+            parse_query("circle of protection: red").unwrap(),
+            Query::And(vec![
+                Query::Name("circle".to_string()),
+                Query::Name("of".to_string()),
+                Query::Name("protection:".to_string()),
+                Query::Name("red".to_string())
+            ])
+        );
+        assert_eq!(
+            parse_query("t: elf").unwrap(),
+            Query::And(vec![
+                Query::Name("t:".to_string()),
+                Query::Name("elf".to_string())
+            ])
+        );
+    }
+
+    #[test]
+    fn parse_filters() {
+        assert_eq!(
+            parse_query("t:creature o:\"draw a card\" -c:u").unwrap(),
+            Query::And(vec![
+                filter("t", Operator::Colon, "creature"),
+                filter("o", Operator::Colon, "draw a card"),
+                Query::Not(Box::new(filter("c", Operator::Colon, "u"))),
+            ])
+        );
+        assert_eq!(
+            parse_query("pow>=4").unwrap(),
+            filter("pow", Operator::GreaterOrEqual, "4")
+        );
+        assert_eq!(
+            parse_query("c!=r").unwrap(),
+            filter("c", Operator::NotEqual, "r")
+        );
+    }
+
+    #[test]
+    fn parse_or_and_parentheses() {
+        assert_eq!(
+            parse_query("(t:elf or t:goblin) lord").unwrap(),
+            Query::And(vec![
+                Query::Or(vec![
+                    filter("t", Operator::Colon, "elf"),
+                    filter("t", Operator::Colon, "goblin"),
+                ]),
+                Query::Name("lord".to_string()),
+            ])
+        );
+        assert_eq!(
+            parse_query("a b OR c").unwrap(),
+            Query::Or(vec![
+                Query::And(vec![
+                    Query::Name("a".to_string()),
+                    Query::Name("b".to_string())
+                ]),
+                Query::Name("c".to_string()),
+            ])
+        );
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert_eq!(parse_query(""), Err(QueryError::EmptyQuery));
+        assert_eq!(parse_query("! \"-\" ..."), Err(QueryError::EmptyQuery));
+        assert_eq!(parse_query("lotus -"), parse_query("lotus"));
+        assert_eq!(parse_query("o:\"draw"), Err(QueryError::UnclosedQuote));
+        assert_eq!(parse_query("(t:elf"), Err(QueryError::UnmatchedParenthesis));
+        assert_eq!(parse_query("t:elf)"), Err(QueryError::UnmatchedParenthesis));
+        assert_eq!(
+            parse_query("elf or"),
+            Err(QueryError::DanglingOperator("or".to_string()))
+        );
+    }
+
+    #[test]
+    fn display_round_trips() {
+        for text in [
+            "t:creature o:\"draw a card\" -c:u",
+            "(t:elf or t:goblin) lord",
+            "-(a or b) !\"Black Lotus\"",
+        ] {
+            let query = parse_query(text).unwrap();
+            assert_eq!(query.to_string(), text);
+            assert_eq!(parse_query(&query.to_string()).unwrap(), query);
+        }
+    }
+
+    #[test]
+    fn without_names_keeps_filters() {
+        let query = parse_query("blakc lotus t:artifact").unwrap();
+        assert_eq!(query.name_words(), vec!["blakc", "lotus"]);
+        assert_eq!(query.without_names().unwrap().to_string(), "t:artifact");
+    }
+
+    #[test]
+    fn with_name_words_keeps_structure() {
+        let query = parse_query("(\"lighning bolt\" or shok) t:instant").unwrap();
+        assert_eq!(query.name_words(), vec!["lighning", "bolt", "shok"]);
+        let words: Vec<String> = ["lightning", "bolt", "shock"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        assert_eq!(
+            query.with_name_words(&words).to_string(),
+            "(\"lightning bolt\" or shock) t:instant"
+        );
+    }
+
+    #[test]
+    fn join_search_args_requotes() {
+        let args: Vec<String> = ["t:creature", "o:draw a card", "-c:u", "black lotus"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        assert_eq!(
+            join_search_args(&args),
+            "t:creature o:\"draw a card\" -c:u \"black lotus\""
+        );
+    }
+
+    #[test]
+    fn fts_expressions() {
+        assert_eq!(
+            fts_expression("exil* \"draw a card\" graveyard").unwrap(),
+            "\"exil\" * \"draw a card\" \"graveyard\""
+        );
+        assert_eq!(fts_expression(" — "), None);
+        assert!(can_use_fts("can't be countered"));
+        assert!(!can_use_fts("{T}: add"));
+    }
+
+    #[test]
+    fn compile_unknown_keyword() {
+        assert_eq!(
+            compile_query(&filter("notakeyword", Operator::Colon, "thing")).err(),
+            Some(QueryError::UnknownKeyword("notakeyword".to_string()))
+        );
+        // Every key the parser makes filters for has to be one the compiler knows
+        for key in FILTER_KEYS {
+            assert_ne!(
+                compile_query(&filter(key, Operator::Colon, "x")).err(),
+                Some(QueryError::UnknownKeyword(key.to_string())),
+            );
+        }
+    }
+}
@@ -0,0 +1,206 @@
+use crate::db::DbCard;
+use crate::normalise::normalise_search_text;
+use textdistance::str::damerau_levenshtein;
+
+// How likely it is a card is the one being searched for when a name search matches lots of
+//  cards. Searching "black" should give Black Lotus before Argivian Blacksmith.
+#[derive(Debug)]
+pub struct ScoredCard {
+    pub card: DbCard,
+    pub score: f64,
+}
+
+const WHOLE_WORD_SCORE: f64 = 10.0;
+const PREFIX_SCORE: f64 = 5.0;
+const CONTAINS_SCORE: f64 = 1.0;
+// Matching the first word of the name is worth this much, the second word half of it, etc.
+const POSITION_SCORE: f64 = 3.0;
+// Taken off per character, so Lightning Bolt beats Lightning Bolt's Younger Brother
+const LENGTH_PENALTY: f64 = 0.05;
+// The most popular card on EDHREC gets this much, falling off to nothing around rank 100000
+const POPULARITY_SCORE: f64 = 3.0;
+const EXACT_NAME_SCORE: f64 = 50.0;
+
+fn split_words(text: &str) -> Vec<&str> {
+    text.split_whitespace().collect()
+}
+
+// The search words are the name words from the query (see Query::name_words)
+pub fn score_card_name(name: &str, search_words: &[String], edhrec_rank: Option<u64>) -> f64 {
+    let name = normalise_search_text(name);
+    let name_words = split_words(&name);
+    let search: Vec<String> = search_words
+        .iter()
+        .map(|w| normalise_search_text(w))
+        .collect();
+
+    let mut score = 0.0;
+    if !search.is_empty() && name == search.join(" ") {
+        score += EXACT_NAME_SCORE;
+    }
+    for word in &search {
+        if let Some(position) = name_words.iter().position(|w| w == word) {
+            score += WHOLE_WORD_SCORE + POSITION_SCORE / (position + 1) as f64;
+        } else if let Some(position) = name_words.iter().position(|w| w.starts_with(word.as_str()))
+        {
+            score += PREFIX_SCORE + POSITION_SCORE / (position + 1) as f64;
+        } else if name.contains(word.as_str()) {
+            score += CONTAINS_SCORE;
+        }
+    }
+    score -= name.chars().count() as f64 * LENGTH_PENALTY;
+    if let Some(rank) = edhrec_rank {
+        score += (POPULARITY_SCORE * (1.0 - (rank.max(1) as f64).log10() / 5.0)).max(0.0);
+    }
+    score
+}
+
+// Normalised, so "jaces" and "Jace's" are the same word
+fn name_tokens(name: &str) -> Vec<String> {
+    normalise_search_text(name)
+        .split_whitespace()
+        .map(|w| w.to_string())
+        .collect()
+}
+
+// How alike a search is to a whole card name, from 0 to 1, ignoring word order and punctuation.
+//  Each search word is paired with the closest word in the name that's no more than max_distance
+//  typos away, so "jaces vryn prodigy" is close to "Jace, Vryn's Prodigy".
+pub fn name_similarity(search: &str, name: &str, max_distance: usize) -> f64 {
+    let search_tokens = name_tokens(search);
+    let name_tokens = name_tokens(name);
+    if search_tokens.is_empty() || name_tokens.is_empty() {
+        return 0.0;
+    }
+    let mut used = vec![false; name_tokens.len()];
+    let mut matched = 0.0;
+    for search_token in &search_tokens {
+        let best = name_tokens
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| !used[*i])
+            .map(|(i, name_token)| (i, damerau_levenshtein(search_token, name_token), name_token))
+            .filter(|(_, distance, _)| *distance <= max_distance)
+            .map(|(i, distance, name_token)| {
+                let longest = search_token.chars().count().max(name_token.chars().count());
+                (i, 1.0 - distance as f64 / longest as f64)
+            })
+            .max_by(|a, b| a.1.total_cmp(&b.1));
+        if let Some((i, similarity)) = best {
+            used[i] = true;
+            matched += similarity;
+        }
+    }
+    2.0 * matched / (search_tokens.len() + name_tokens.len()) as f64
+}
+
+// Best matches first. Cards with the same score stay in the order they came in.
+pub fn rank_cards(cards: Vec<DbCard>, search_words: &[String]) -> Vec<ScoredCard> {
+    let mut scored = score_cards(cards, search_words);
+    scored.sort_by(|a, b| b.score.total_cmp(&a.score));
+    scored
+}
+
+// Scores without changing the order, for when the search asked for a particular order
+pub fn score_cards(cards: Vec<DbCard>, search_words: &[String]) -> Vec<ScoredCard> {
+    cards
+        .into_iter()
+        .map(|card| ScoredCard {
+            score: score_card_name(&card.full_name(), search_words, card.edhrec_rank),
+            card,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn words(search: &str) -> Vec<String> {
+        search.split_whitespace().map(|w| w.to_string()).collect()
+    }
+
+    fn card(name: &str, edhrec_rank: Option<u64>) -> DbCard {
+        DbCard {
+            name: name.to_string(),
+            edhrec_rank,
+            ..Default::default()
+        }
+    }
+
+    fn ranked_names(names: &[&str], search: &str) -> Vec<String> {
+        let cards = names.iter().map(|n| card(n, None)).collect();
+        rank_cards(cards, &words(search))
+            .into_iter()
+            .map(|s| s.card.name)
+            .collect()
+    }
+
+    #[test]
+    fn whole_words_beat_prefixes() {
+        assert_eq!(
+            ranked_names(&["Argivian Blacksmith", "Black Lotus"], "black"),
+            vec!["Black Lotus", "Argivian Blacksmith"]
+        );
+    }
+
+    #[test]
+    fn earlier_words_rank_higher() {
+        assert_eq!(
+            ranked_names(&["Ballad of the Black Flag", "Black Knight"], "black"),
+            vec!["Black Knight", "Ballad of the Black Flag"]
+        );
+    }
+
+    #[test]
+    fn shorter_names_rank_higher() {
+        assert_eq!(
+            ranked_names(
+                &["Lightning Bolt's Younger Brother", "Lightning Bolt"],
+                "lightning"
+            ),
+            vec!["Lightning Bolt", "Lightning Bolt's Younger Brother"]
+        );
+    }
+
+    #[test]
+    fn exact_name_wins() {
+        assert_eq!(
+            ranked_names(&["Blacker Lotus", "Black Lotus"], "black lotus"),
+            vec!["Black Lotus", "Blacker Lotus"]
+        );
+        assert!(
+            score_card_name("Lim-Dûl's Vault", &words("lim-dul's vault"), None)
+                > score_card_name("Lim-Dûl's High Guard", &words("lim-dul's vault"), None)
+        );
+    }
+
+    #[test]
+    fn similar_names() {
+        let jace = "Jace, Vryn's Prodigy";
+        assert_eq!(name_similarity("jace vryn's prodigy", jace, 2), 1.0);
+        assert!(name_similarity("jaces vryn prodigy", jace, 2) > 0.8);
+        assert!(name_similarity("prodigy jace vryns", jace, 2) > 0.8);
+        assert!(name_similarity("jcae vyrn prodgiy", jace, 2) > 0.6);
+        assert!(
+            name_similarity("jaces vryn prodigy", jace, 2)
+                > name_similarity("jaces vryn prodigy", "Jace, the Mind Sculptor", 2)
+        );
+        assert_eq!(name_similarity("lotus", jace, 2), 0.0);
+        assert_eq!(name_similarity("", jace, 2), 0.0);
+    }
+
+    #[test]
+    fn popular_cards_break_ties() {
+        let cards = vec![
+            card("Elvish Hunter", Some(9000)),
+            card("Elvish Mystic", Some(20)),
+        ];
+        let ranked = rank_cards(cards, &words("elvish"));
+        assert_eq!(ranked[0].card.name, "Elvish Mystic");
+        assert!(
+            score_card_name("Sol Ring", &words("sol"), Some(1))
+                > score_card_name("Sol Ring", &words("sol"), None)
+        );
+    }
+}
@@ -0,0 +1,42 @@
+// Power, toughness, loyalty and defense come from Scryfall as text, because of cards like
+//  Tarmogoyf (*/1+*) and Little Girl (.5/.5). Like Scryfall, anything involving * or X counts
+//  as 0 plus whatever number is printed next to it.
+
+// Returns None for values that can't be compared, like ? or ∞
+pub fn parse_stat(stat: &str) -> Option<f64> {
+    let stat = stat.trim();
+    if stat == "?" {
+        return None;
+    }
+    let number = stat
+        .replace(['*', 'X', '²'], "")
+        .trim_end_matches(['+', '-'])
+        .to_string();
+    if number.is_empty() {
+        return Some(0.0);
+    }
+    number.parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_numbers() {
+        assert_eq!(parse_stat("3"), Some(3.0));
+        assert_eq!(parse_stat(".5"), Some(0.5));
+        assert_eq!(parse_stat("-1"), Some(-1.0));
+    }
+
+    #[test]
+    fn parse_variable_stats() {
+        assert_eq!(parse_stat("*"), Some(0.0));
+        assert_eq!(parse_stat("1+*"), Some(1.0));
+        assert_eq!(parse_stat("7-*"), Some(7.0));
+        assert_eq!(parse_stat("*²"), Some(0.0));
+        assert_eq!(parse_stat("X"), Some(0.0));
+        assert_eq!(parse_stat("?"), None);
+        assert_eq!(parse_stat("∞"), None);
+    }
+}
@@ -0,0 +1,155 @@
+// Splits type lines like "Legendary Creature — Elf Scout" into their supertypes (Legendary),
+//  card types (Creature) and subtypes (Elf, Scout).
+// https://mtg.fandom.com/wiki/Type_line
+
+// Token isn't really a supertype, but Scryfall puts it where the supertypes go
+const SUPERTYPES: [&str; 8] = [
+    "Basic",
+    "Legendary",
+    "Ongoing",
+    "Snow",
+    "World",
+    "Elite",
+    "Host",
+    "Token",
+];
+
+// Subtypes are normally one word, but not always
+const MULTI_WORD_SUBTYPES: [&str; 1] = ["Time Lord"];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TypeKind {
+    Supertype,
+    CardType,
+    Subtype,
+}
+
+impl TypeKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TypeKind::Supertype => "supertype",
+            TypeKind::CardType => "type",
+            TypeKind::Subtype => "subtype",
+        }
+    }
+}
+
+#[derive(Debug, Default, PartialEq)]
+pub struct TypeLine {
+    pub supertypes: Vec<String>,
+    pub card_types: Vec<String>,
+    pub subtypes: Vec<String>,
+}
+
+impl TypeLine {
+    pub fn all(&self) -> Vec<(TypeKind, &str)> {
+        let mut all = Vec::new();
+        all.extend(
+            self.supertypes
+                .iter()
+                .map(|t| (TypeKind::Supertype, t.as_str())),
+        );
+        all.extend(
+            self.card_types
+                .iter()
+                .map(|t| (TypeKind::CardType, t.as_str())),
+        );
+        all.extend(
+            self.subtypes
+                .iter()
+                .map(|t| (TypeKind::Subtype, t.as_str())),
+        );
+        all
+    }
+
+    pub fn has_card_type(&self, card_type: &str) -> bool {
+        self.card_types.iter().any(|t| t == card_type)
+    }
+
+    pub fn has_supertype(&self, supertype: &str) -> bool {
+        self.supertypes.iter().any(|t| t == supertype)
+    }
+}
+
+fn split_subtypes(subtypes: &str) -> Vec<String> {
+    let mut split = Vec::new();
+    let mut rest = subtypes.trim();
+    'outer: while !rest.is_empty() {
+        for multi_word in MULTI_WORD_SUBTYPES {
+            if let Some(after) = rest.strip_prefix(multi_word)
+                && (after.is_empty() || after.starts_with(' '))
+            {
+                split.push(multi_word.to_string());
+                rest = after.trim_start();
+                continue 'outer;
+            }
+        }
+        let (word, after) = rest.split_once(' ').unwrap_or((rest, ""));
+        split.push(word.to_string());
+        rest = after.trim_start();
+    }
+    split
+}
+
+// Only deals with a single face - use split_faces first for something like "Instant // Sorcery"
+pub fn parse_type_line(type_line: &str) -> TypeLine {
+    let (types, subtypes) = match type_line.split_once('—') {
+        Some((types, subtypes)) => (types, subtypes),
+        None => (type_line, ""),
+    };
+    let mut parsed = TypeLine::default();
+    for word in types.split_whitespace() {
+        if SUPERTYPES.contains(&word) {
+            parsed.supertypes.push(word.to_string());
+        } else {
+            parsed.card_types.push(word.to_string());
+        }
+    }
+    parsed.subtypes = split_subtypes(subtypes);
+    parsed
+}
+
+// Top level type lines of multi-faced cards have every face in them
+pub fn split_faces(type_line: &str) -> impl Iterator<Item = &str> {
+    type_line.split(" // ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_legendary_creature() {
+        let parsed = parse_type_line("Legendary Creature — Elf Scout");
+        assert_eq!(parsed.supertypes, vec!["Legendary"]);
+        assert_eq!(parsed.card_types, vec!["Creature"]);
+        assert_eq!(parsed.subtypes, vec!["Elf", "Scout"]);
+    }
+
+    #[test]
+    fn parse_without_subtypes() {
+        let parsed = parse_type_line("Artifact");
+        assert_eq!(parsed.card_types, vec!["Artifact"]);
+        assert!(parsed.supertypes.is_empty());
+        assert!(parsed.subtypes.is_empty());
+    }
+
+    #[test]
+    fn plane_is_not_planeswalker() {
+        assert!(parse_type_line("Plane — Alara").has_card_type("Plane"));
+        assert!(!parse_type_line("Legendary Planeswalker — Jace").has_card_type("Plane"));
+    }
+
+    #[test]
+    fn parse_token_and_multi_word_subtypes() {
+        let parsed = parse_type_line("Token Legendary Creature — Time Lord Doctor");
+        assert!(parsed.has_supertype("Token"));
+        assert_eq!(parsed.subtypes, vec!["Time Lord", "Doctor"]);
+    }
+
+    #[test]
+    fn split_double_faced_type_lines() {
+        let faces: Vec<&str> = split_faces("Creature — Human // Creature — Werewolf").collect();
+        assert_eq!(faces, vec!["Creature — Human", "Creature — Werewolf"]);
+    }
+}